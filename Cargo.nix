@@ -112,6 +112,13 @@ rec {
     build = "build.rs";
     inherit dependencies buildDependencies features;
   };
+  itoa_0_4_3_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "itoa";
+    version = "0.4.3";
+    authors = [ "David Tolnay <dtolnay@gmail.com>" ];
+    sha256 = "0zadimmdgvili3gdwxqg7ljv3r4wcdg1kkdfp9nl15vnm23vrhy1";
+    inherit dependencies buildDependencies features;
+  };
   libc_0_2_43_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
     crateName = "libc";
     version = "0.2.43";
@@ -141,6 +148,15 @@ rec {
     build = "build.rs";
     inherit dependencies buildDependencies features;
   };
+  pulldown_cmark_0_2_0_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "pulldown-cmark";
+    version = "0.2.0";
+    authors = [ "Raph Levien <raph.levien@gmail.com>" ];
+    sha256 = "1vsqbbymps71gjzhj9v6w3nbarrbyi6cd743x4nkvi166wr6dcvk";
+    build = "build.rs";
+    crateBin = [];
+    inherit dependencies buildDependencies features;
+  };
   quote_0_6_8_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
     crateName = "quote";
     version = "0.6.8";
@@ -183,6 +199,37 @@ rec {
     sha256 = "00ma4r9haq0zv5krps617mym6y74056pfcivyld0kpci156vfaax";
     inherit dependencies buildDependencies features;
   };
+  ryu_0_2_7_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "ryu";
+    version = "0.2.7";
+    authors = [ "David Tolnay <dtolnay@gmail.com>" ];
+    sha256 = "0m8szf1m87wfqkwh1f9zp9bn2mb0m9nav028xxnd0hlig90b44bd";
+    build = "build.rs";
+    inherit dependencies buildDependencies features;
+  };
+  serde_1_0_80_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "serde";
+    version = "1.0.80";
+    authors = [ "Erick Tryzelaar <erick.tryzelaar@gmail.com>" "David Tolnay <dtolnay@gmail.com>" ];
+    sha256 = "0vyciw2qhrws4hz87pfnsjdfzzdw2sclxqxq394g3a219a2rdcxz";
+    build = "build.rs";
+    inherit dependencies buildDependencies features;
+  };
+  serde_derive_1_0_80_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "serde_derive";
+    version = "1.0.80";
+    authors = [ "Erick Tryzelaar <erick.tryzelaar@gmail.com>" "David Tolnay <dtolnay@gmail.com>" ];
+    sha256 = "1akvzhbnnqhd92lfj7vp43scs1vdml7x27c82l5yh0kz7xf7jaky";
+    procMacro = true;
+    inherit dependencies buildDependencies features;
+  };
+  serde_json_1_0_33_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "serde_json";
+    version = "1.0.33";
+    authors = [ "Erick Tryzelaar <erick.tryzelaar@gmail.com>" "David Tolnay <dtolnay@gmail.com>" ];
+    sha256 = "1cahjwpa723cphwpwk9dzhpx4mvfafvfml428n11cj4q69vjs7y5";
+    inherit dependencies buildDependencies features;
+  };
   smol_str_0_1_7_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
     crateName = "smol_str";
     version = "0.1.7";
@@ -240,6 +287,13 @@ rec {
     sha256 = "1s8d5cna12smhgj0x2y1xphklyk2an1yzbadnj89p1vy5vnjpsas";
     inherit dependencies buildDependencies features;
   };
+  toml_0_4_10_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
+    crateName = "toml";
+    version = "0.4.10";
+    authors = [ "Alex Crichton <alex@alexcrichton.com>" ];
+    sha256 = "0fs4kxl86w3kmgwcgcv23nk79zagayz1spg281r83w0ywf88d6f1";
+    inherit dependencies buildDependencies features;
+  };
   unicode_width_0_1_5_ = { dependencies?[], buildDependencies?[], features?[] }: buildRustCrate {
     crateName = "unicode-width";
     version = "0.1.5";
@@ -554,6 +608,16 @@ rec {
     syn_0_15_15.default = true;
     synstructure_0_10_1.default = true;
   }) [ proc_macro2_0_4_20_features quote_0_6_8_features syn_0_15_15_features synstructure_0_10_1_features ];
+  itoa_0_4_3 = { features?(itoa_0_4_3_features {}) }: itoa_0_4_3_ {
+    features = mkFeatures (features.itoa_0_4_3 or {});
+  };
+  itoa_0_4_3_features = f: updateFeatures f (rec {
+    itoa_0_4_3.default = (f.itoa_0_4_3.default or true);
+    itoa_0_4_3.std =
+      (f.itoa_0_4_3.std or false) ||
+      (f.itoa_0_4_3.default or false) ||
+      (itoa_0_4_3.default or false);
+  }) [];
   libc_0_2_43 = { features?(libc_0_2_43_features {}) }: libc_0_2_43_ {
     features = mkFeatures (features.libc_0_2_43 or {});
   };
@@ -565,15 +629,21 @@ rec {
       (libc_0_2_43.default or false);
   }) [];
   nixdoc_1_0_1 = { features?(nixdoc_1_0_1_features {}) }: nixdoc_1_0_1_ {
-    dependencies = mapFeatures features ([ failure_0_1_3 rnix_0_4_1 structopt_0_2_12 xml_rs_0_8_0 ]);
+    dependencies = mapFeatures features ([ failure_0_1_3 failure_derive_0_1_3 pulldown_cmark_0_2_0 rnix_0_4_1 serde_1_0_80 serde_derive_1_0_80 serde_json_1_0_33 structopt_0_2_12 toml_0_4_10 xml_rs_0_8_0 ]);
   };
   nixdoc_1_0_1_features = f: updateFeatures f (rec {
     failure_0_1_3.default = true;
+    failure_derive_0_1_3.default = true;
     nixdoc_1_0_1.default = (f.nixdoc_1_0_1.default or true);
+    pulldown_cmark_0_2_0.default = (f.pulldown_cmark_0_2_0.default or false);
     rnix_0_4_1.default = true;
+    serde_1_0_80.default = true;
+    serde_derive_1_0_80.default = true;
+    serde_json_1_0_33.default = true;
     structopt_0_2_12.default = true;
+    toml_0_4_10.default = true;
     xml_rs_0_8_0.default = true;
-  }) [ failure_0_1_3_features rnix_0_4_1_features structopt_0_2_12_features xml_rs_0_8_0_features ];
+  }) [ failure_0_1_3_features failure_derive_0_1_3_features pulldown_cmark_0_2_0_features rnix_0_4_1_features serde_1_0_80_features serde_derive_1_0_80_features serde_json_1_0_33_features structopt_0_2_12_features toml_0_4_10_features xml_rs_0_8_0_features ];
   nodrop_0_1_12 = { features?(nodrop_0_1_12_features {}) }: nodrop_0_1_12_ {
     dependencies = mapFeatures features ([]);
     features = mkFeatures (features.nodrop_0_1_12 or {});
@@ -603,6 +673,18 @@ rec {
     proc_macro2_0_4_20.default = (f.proc_macro2_0_4_20.default or true);
     unicode_xid_0_1_0.default = true;
   }) [ unicode_xid_0_1_0_features ];
+  pulldown_cmark_0_2_0 = { features?(pulldown_cmark_0_2_0_features {}) }: pulldown_cmark_0_2_0_ {
+    dependencies = mapFeatures features ([ bitflags_1_0_4 ]);
+    features = mkFeatures (features.pulldown_cmark_0_2_0 or {});
+  };
+  pulldown_cmark_0_2_0_features = f: updateFeatures f (rec {
+    bitflags_1_0_4.default = true;
+    pulldown_cmark_0_2_0.default = (f.pulldown_cmark_0_2_0.default or true);
+    pulldown_cmark_0_2_0.getopts =
+      (f.pulldown_cmark_0_2_0.getopts or false) ||
+      (f.pulldown_cmark_0_2_0.default or false) ||
+      (pulldown_cmark_0_2_0.default or false);
+  }) [ bitflags_1_0_4_features ];
   quote_0_6_8 = { features?(quote_0_6_8_features {}) }: quote_0_6_8_ {
     dependencies = mapFeatures features ([ proc_macro2_0_4_20 ]);
     features = mkFeatures (features.quote_0_6_8 or {});
@@ -650,6 +732,59 @@ rec {
   rustc_demangle_0_1_9_features = f: updateFeatures f (rec {
     rustc_demangle_0_1_9.default = (f.rustc_demangle_0_1_9.default or true);
   }) [];
+  ryu_0_2_7 = { features?(ryu_0_2_7_features {}) }: ryu_0_2_7_ {
+    dependencies = mapFeatures features ([]);
+    features = mkFeatures (features.ryu_0_2_7 or {});
+  };
+  ryu_0_2_7_features = f: updateFeatures f (rec {
+    ryu_0_2_7.default = (f.ryu_0_2_7.default or true);
+  }) [];
+  serde_1_0_80 = { features?(serde_1_0_80_features {}) }: serde_1_0_80_ {
+    dependencies = mapFeatures features ([ ]
+      ++ (if features.serde_1_0_80.serde_derive or false then [ serde_derive_1_0_80 ] else []));
+    features = mkFeatures (features.serde_1_0_80 or {});
+  };
+  serde_1_0_80_features = f: updateFeatures f (rec {
+    serde_1_0_80.default = (f.serde_1_0_80.default or true);
+    serde_1_0_80.serde_derive =
+      (f.serde_1_0_80.serde_derive or false) ||
+      (f.serde_1_0_80.derive or false) ||
+      (serde_1_0_80.derive or false);
+    serde_1_0_80.std =
+      (f.serde_1_0_80.std or false) ||
+      (f.serde_1_0_80.default or false) ||
+      (serde_1_0_80.default or false);
+    serde_1_0_80.unstable =
+      (f.serde_1_0_80.unstable or false) ||
+      (f.serde_1_0_80.alloc or false) ||
+      (serde_1_0_80.alloc or false);
+    serde_derive_1_0_80.default = true;
+  }) [ serde_derive_1_0_80_features ];
+  serde_derive_1_0_80 = { features?(serde_derive_1_0_80_features {}) }: serde_derive_1_0_80_ {
+    dependencies = mapFeatures features ([ proc_macro2_0_4_20 quote_0_6_8 syn_0_15_15 ]);
+    features = mkFeatures (features.serde_derive_1_0_80 or {});
+  };
+  serde_derive_1_0_80_features = f: updateFeatures f (rec {
+    proc_macro2_0_4_20.default = true;
+    quote_0_6_8.default = true;
+    serde_derive_1_0_80.default = (f.serde_derive_1_0_80.default or true);
+    syn_0_15_15.default = true;
+    syn_0_15_15.visit = true;
+  }) [ proc_macro2_0_4_20_features quote_0_6_8_features syn_0_15_15_features ];
+  serde_json_1_0_33 = { features?(serde_json_1_0_33_features {}) }: serde_json_1_0_33_ {
+    dependencies = mapFeatures features ([ itoa_0_4_3 ryu_0_2_7 serde_1_0_80 ]);
+    features = mkFeatures (features.serde_json_1_0_33 or {});
+  };
+  serde_json_1_0_33_features = f: updateFeatures f (rec {
+    itoa_0_4_3.default = true;
+    ryu_0_2_7.default = true;
+    serde_1_0_80.default = true;
+    serde_json_1_0_33.default = (f.serde_json_1_0_33.default or true);
+    serde_json_1_0_33.indexmap =
+      (f.serde_json_1_0_33.indexmap or false) ||
+      (f.serde_json_1_0_33.preserve_order or false) ||
+      (serde_json_1_0_33.preserve_order or false);
+  }) [ itoa_0_4_3_features ryu_0_2_7_features serde_1_0_80_features ];
   smol_str_0_1_7 = { features?(smol_str_0_1_7_features {}) }: smol_str_0_1_7_ {
     dependencies = mapFeatures features ([]);
   };
@@ -796,6 +931,13 @@ rec {
     textwrap_0_10_0.default = (f.textwrap_0_10_0.default or true);
     unicode_width_0_1_5.default = true;
   }) [ unicode_width_0_1_5_features ];
+  toml_0_4_10 = { features?(toml_0_4_10_features {}) }: toml_0_4_10_ {
+    dependencies = mapFeatures features ([ serde_1_0_80 ]);
+  };
+  toml_0_4_10_features = f: updateFeatures f (rec {
+    serde_1_0_80.default = true;
+    toml_0_4_10.default = (f.toml_0_4_10.default or true);
+  }) [ serde_1_0_80_features ];
   unicode_width_0_1_5 = { features?(unicode_width_0_1_5_features {}) }: unicode_width_0_1_5_ {
     features = mkFeatures (features.unicode_width_0_1_5 or {});
   };
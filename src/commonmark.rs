@@ -0,0 +1,256 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements CommonMark output for the same manual
+//! entry structures that are rendered to DocBook in `docbook.rs`.
+//!
+//! Headings carry explicit `{#anchor}` attributes using the same
+//! identifiers as the DocBook output, so links into the manual keep
+//! working regardless of which backend produced it.
+
+use std::io::Write;
+use failure::Error;
+use docbook::{Argument, ManualEntry, SegmentKind};
+
+/// Write the notice at the top of generated documents.
+pub fn write_notice_md<W: Write>(w: &mut W) -> Result<(), Error> {
+    writeln!(w, "<!-- Do not edit this file manually!")?;
+    writeln!(w)?;
+    writeln!(w, "This file was generated using nixdoc[1]. Please edit the source Nix")?;
+    writeln!(w, "file from which this Markdown was generated instead.")?;
+    writeln!(w)?;
+    writeln!(w, "[1]: https://github.com/tazjin/nixdoc")?;
+    writeln!(w, "-->")?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write the header of a category section, the CommonMark
+/// equivalent of the `<section>`/`<title>` pair in the DocBook
+/// output.
+pub fn write_category_md<W: Write>(w: &mut W,
+                                   category: &str,
+                                   description: &str) -> Result<(), Error> {
+    writeln!(w, "# {} {{#sec-functions-library-{}}}", description, category)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write the header of the section containing the internal entries
+/// of a category.
+pub fn write_internal_md<W: Write>(w: &mut W, category: &str) -> Result<(), Error> {
+    writeln!(w, "## Internal functions {{#sec-functions-library-{}-internal}}", category)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write a fenced code block, choosing a fence that is longer than
+/// any run of backticks inside of the content.
+fn code_block<W: Write>(w: &mut W, lang: &str, content: &str) -> Result<(), Error> {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in content.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+
+    let fence = "`".repeat(longest.max(2) + 1);
+    writeln!(w, "{}{}", fence, lang)?;
+    writeln!(w, "{}", content.trim_end())?;
+    writeln!(w, "{}", fence)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+impl Argument {
+    /// Write CommonMark list items for a single function argument.
+    fn write_argument_md<W: Write>(self, w: &mut W, indent: usize) -> Result<(), Error> {
+        let prefix = " ".repeat(indent);
+
+        match self {
+            Argument::Flat(arg) => {
+                let doc = arg.doc.unwrap_or("Function argument".into());
+                writeln!(w, "{}- `{}`: {}", prefix, arg.name, doc.trim())?;
+            },
+
+            Argument::Pattern(pattern_args) => {
+                writeln!(w, "{}- `pattern`: Structured function argument", prefix)?;
+                for pattern_arg in pattern_args {
+                    Argument::Flat(pattern_arg)
+                        .write_argument_md(w, indent + 2)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+impl ManualEntry {
+    /// Write a single CommonMark entry for a documented Nix function.
+    /// Entries of nested attribute sets get headings of a deeper
+    /// level, as far as CommonMark allows.
+    pub fn write_section_md<W: Write>(self, w: &mut W) -> Result<(), Error> {
+        let title = self.title();
+        let level = (2 + self.path.len() + self.internal as usize).min(6);
+        let heading = "#".repeat(level);
+        let subheading = "#".repeat((level + 1).min(6));
+
+        writeln!(w, "{} `{}` {{#{}}}", heading, title, self.anchor())?;
+        writeln!(w)?;
+
+        // Type signature
+        if let Some(t) = &self.fn_type {
+            code_block(w, "", t)?;
+        }
+
+        // Reference to the original definition of re-exported entries
+        if let Some(alias) = &self.alias {
+            match self.alias_anchor() {
+                Some(anchor) => writeln!(w, "Alias of [`{}`](#{}).", alias.name, anchor)?,
+                None => writeln!(w, "Alias of `{}`.", alias.name)?,
+            }
+            writeln!(w)?;
+        }
+
+        // Primary doc string
+        if !self.description.is_empty() {
+            writeln!(w, "{}", self.description)?;
+            writeln!(w)?;
+        }
+
+        // Function argument names
+        if !self.args.is_empty() {
+            writeln!(w, "{} Arguments", subheading)?;
+            writeln!(w)?;
+
+            for arg in self.args {
+                arg.write_argument_md(w, 0)?;
+            }
+
+            writeln!(w)?;
+        }
+
+        // Example program listings (if applicable)
+        for example in &self.examples {
+            match &example.title {
+                Some(example_title) => writeln!(w, "{} `{}`: {}", subheading, title, example_title)?,
+                None => writeln!(w, "{} `{}` usage example", subheading, title)?,
+            }
+            writeln!(w)?;
+
+            if example.has_results() {
+                for segment in example.segments() {
+                    if segment.kind == SegmentKind::Result {
+                        writeln!(w, "Result:")?;
+                        writeln!(w)?;
+                    }
+                    code_block(w, "nix", &segment.text)?;
+                }
+            } else {
+                code_block(w, "nix", &example.code)?;
+            }
+        }
+
+        // Link to the function location
+        if let Some(location) = &self.location {
+            match &self.location_url {
+                Some(url) => writeln!(w, "Located at [`{}`]({}).", location.display(), url)?,
+                None => writeln!(w, "Located at `{}`.", location.display())?,
+            }
+            writeln!(w)?;
+        }
+
+        // Members of nested attribute sets
+        for child in self.children {
+            child.write_section_md(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docbook::{Alias, Example, SingleArg};
+
+    fn render(entry: ManualEntry) -> String {
+        let mut out = vec![];
+        entry.write_section_md(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn entry(name: &str) -> ManualEntry {
+        ManualEntry {
+            category: "strings".into(),
+            name: name.into(),
+            title_prefix: "lib.".into(),
+            id_prefix: "function-library-".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn description_and_arguments() {
+        let md = render(ManualEntry {
+            fn_type: Some("concatSep :: string -> [string] -> string".into()),
+            description: "Concatenate strings with a separator.".into(),
+            args: vec![
+                Argument::Flat(SingleArg { name: "sep".into(), doc: Some("The separator".into()) }),
+                Argument::Pattern(vec![SingleArg { name: "list".into(), doc: None }]),
+            ],
+            ..entry("concatSep")
+        });
+
+        assert_eq!(md, "## `lib.strings.concatSep` {#function-library-lib.strings.concatSep}\n\n\
+                        ```\nconcatSep :: string -> [string] -> string\n```\n\n\
+                        Concatenate strings with a separator.\n\n\
+                        ### Arguments\n\n\
+                        - `sep`: The separator\n\
+                        - `pattern`: Structured function argument\n  \
+                        - `list`: Function argument\n\n");
+    }
+
+    #[test]
+    fn example_with_result() {
+        let md = render(ManualEntry {
+            examples: vec![Example { title: None, code: "concat [ \"a\" ]\n=> \"a\"".into() }],
+            ..entry("concat")
+        });
+
+        assert!(md.ends_with("### `lib.strings.concat` usage example\n\n\
+                              ```nix\nconcat [ \"a\" ]\n```\n\n\
+                              Result:\n\n```nix\n\"a\"\n```\n\n"), "{}", md);
+    }
+
+    #[test]
+    fn alias() {
+        let md = render(ManualEntry {
+            alias: Some(Alias {
+                name: "lib.lists.head".into(),
+                category: Some("lists".into()),
+                ident: Some("lib.lists.head".into()),
+            }),
+            ..entry("head")
+        });
+
+        assert!(md.contains("Alias of [`lib.lists.head`](#function-library-lib.lists.head).\n"), "{}", md);
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module reads the project configuration (`nixdoc.toml`), which
+//! lists the files of a library with their categories and holds the
+//! settings that would otherwise have to be passed on every run.
+//! Command line flags take precedence over the configuration.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::de::{self, Deserialize, Deserializer};
+use toml;
+
+use error::NixdocError;
+use Format;
+
+/// Name of the configuration file that is read from the current
+/// directory if no other file is given.
+pub const CONFIG_FILE: &str = "nixdoc.toml";
+
+/// A file of the library.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// Path of the file. Relative paths are relative to the directory
+    /// containing the configuration file.
+    pub path: PathBuf,
+
+    /// Name of the category, defaults to the file name without its
+    /// extension.
+    pub category: Option<String>,
+
+    /// Description of the category.
+    pub description: Option<String>,
+}
+
+impl FileConfig {
+    /// Settings of a file that is not listed in the configuration.
+    pub fn new(path: PathBuf) -> FileConfig {
+        FileConfig {
+            path,
+            category: None,
+            description: None,
+        }
+    }
+}
+
+/// Contents of the configuration file. Settings that are not present
+/// fall back to the command line flags' defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub files: Vec<FileConfig>,
+    pub title_prefix: Option<String>,
+    pub id_prefix: Option<String>,
+    pub override_dir: Option<String>,
+    pub locations_include: Option<String>,
+    pub type_url: Option<String>,
+    pub revision: Option<String>,
+
+    /// Root directory of the repository, see `--repo-root`. Relative
+    /// to the directory containing the configuration file.
+    pub repo_root: Option<PathBuf>,
+
+    #[serde(deserialize_with = "deserialize_format")]
+    pub format: Option<Format>,
+}
+
+/// Deserialize an output format from its name, as accepted by `--format`.
+fn deserialize_format<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Format>, D::Error> {
+    match Option::<String>::deserialize(d)? {
+        Some(name) => name.parse().map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+impl Config {
+    /// Read the configuration from the given file.
+    pub fn load(path: &Path) -> Result<Config, NixdocError> {
+        let src = fs::read_to_string(path).map_err(|e| NixdocError::read(path, e))?;
+        let mut config: Config = toml::from_str(&src).map_err(|e| NixdocError::Config {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for file in &mut config.files {
+            file.path = dir.join(&file.path);
+        }
+        config.repo_root = config.repo_root.map(|root| dir.join(root));
+
+        Ok(config)
+    }
+
+    /// Settings of the given file, if it is listed in the configuration.
+    pub fn file(&self, path: &Path) -> Option<&FileConfig> {
+        let canonical = fs::canonicalize(path).ok();
+        self.files.iter().find(|file| {
+            file.path == path || (canonical.is_some() && fs::canonicalize(&file.path).ok() == canonical)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Write a configuration file into an empty directory and load it.
+    fn load(name: &str, src: &str) -> (PathBuf, Result<Config, NixdocError>) {
+        let dir = env::temp_dir().join(format!("nixdoc-config-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, src).unwrap();
+        let config = Config::load(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn unknown_fields() {
+        for src in &["title-prefix = \"pkgs.\"", "[[files]]\npath = \"a.nix\"\nname = \"a\""] {
+            match load("unknown", src).1 {
+                Err(NixdocError::Config { message, .. }) => assert!(message.contains("unknown field"), "{}", message),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn relative_paths() {
+        let src = "repo_root = \"..\"\nrevision = \"18.09\"\nformat = \"man\"\n\n\
+                   [[files]]\npath = \"lib/strings.nix\"\ncategory = \"strings\"\n\n\
+                   [[files]]\npath = \"/nix/lists.nix\"\n";
+        let (dir, config) = load("relative", src);
+        let config = config.unwrap();
+
+        assert_eq!(config.repo_root, Some(dir.join("..")));
+        assert_eq!(config.revision, Some("18.09".into()));
+        assert_eq!(config.format, Some(Format::Man));
+        assert_eq!(config.files.iter().map(|f| f.path.clone()).collect::<Vec<_>>(),
+                   vec![dir.join("lib/strings.nix"), PathBuf::from("/nix/lists.nix")]);
+        assert_eq!(config.files[0].category, Some("strings".into()));
+        assert!(config.file(&dir.join("lib/strings.nix")).is_some());
+    }
+}
@@ -0,0 +1,275 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module computes how much of the exported attributes of a
+//! library is documented, so that CI can reject undocumented
+//! additions.
+//!
+//! Coverage is computed from categories parsed with
+//! `ParseOptions::include_undocumented` set, as otherwise only
+//! documented attributes are extracted in the first place.
+
+use std::io::Write;
+use failure::Error;
+
+use docbook::{Argument, SingleArg};
+use error::NixdocError;
+use {Category, DocItem};
+
+/// Documentation status of an exported attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// The attribute has a description.
+    Documented,
+
+    /// The attribute has no description.
+    Undocumented,
+
+    /// The attribute is re-exported with `inherit` without a comment
+    /// of its own. It is documented by its original definition and
+    /// does not count towards the coverage.
+    Alias,
+}
+
+/// Coverage of a single exported attribute.
+#[derive(Debug, Clone)]
+pub struct ItemCoverage {
+    /// Attribute path of the item within its category.
+    pub name: String,
+
+    /// Whether the attribute itself is documented.
+    pub status: Status,
+
+    /// Number of function arguments, counting each attribute of a
+    /// pattern argument separately.
+    pub args: usize,
+
+    /// Number of documented function arguments.
+    pub documented_args: usize,
+
+    /// Names of the arguments without documentation.
+    pub undocumented_args: Vec<String>,
+}
+
+/// Coverage of a category, or of several categories combined.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// Name of the category, empty for combined totals.
+    pub name: String,
+
+    /// Coverage of each exported attribute.
+    pub items: Vec<ItemCoverage>,
+}
+
+/// Format a ratio as a percentage. Nothing to document counts as
+/// fully documented.
+fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        100.0 * part as f64 / total as f64
+    }
+}
+
+fn single_args(args: &[Argument]) -> Vec<&SingleArg> {
+    let mut result = vec![];
+    for arg in args {
+        match arg {
+            Argument::Flat(single) => result.push(single),
+            Argument::Pattern(pattern) => result.extend(pattern.iter()),
+        }
+    }
+    result
+}
+
+impl ItemCoverage {
+    fn from_item(item: &DocItem) -> ItemCoverage {
+        let status = if !item.comment.doc.trim().is_empty() {
+            Status::Documented
+        } else if item.inherited_from.is_some() {
+            Status::Alias
+        } else {
+            Status::Undocumented
+        };
+
+        let args = single_args(&item.args);
+        let undocumented_args: Vec<String> = args.iter()
+            .filter(|arg| arg.doc.as_ref().filter(|doc| !doc.trim().is_empty()).is_none())
+            .map(|arg| arg.name.clone())
+            .collect();
+
+        ItemCoverage {
+            name: item.attr_path(),
+            status,
+            args: args.len(),
+            documented_args: args.len() - undocumented_args.len(),
+            undocumented_args,
+        }
+    }
+}
+
+impl Coverage {
+    /// Compute the coverage of the exported attributes of a category.
+    /// Internal bindings are not taken into account.
+    pub fn of_category(category: &Category) -> Coverage {
+        Coverage {
+            name: category.name.clone(),
+            items: category.items.iter()
+                .filter(|item| !item.internal)
+                .map(ItemCoverage::from_item)
+                .collect(),
+        }
+    }
+
+    /// Combine the coverage of several categories.
+    pub fn total(categories: &[Coverage]) -> Coverage {
+        Coverage {
+            name: String::new(),
+            items: categories.iter().flat_map(|c| c.items.iter().cloned()).collect(),
+        }
+    }
+
+    /// Number of attributes that count towards the coverage.
+    pub fn entries(&self) -> usize {
+        self.items.iter().filter(|item| item.status != Status::Alias).count()
+    }
+
+    /// Number of documented attributes.
+    pub fn documented(&self) -> usize {
+        self.items.iter().filter(|item| item.status == Status::Documented).count()
+    }
+
+    /// Percentage of documented attributes.
+    pub fn percentage(&self) -> f64 {
+        percentage(self.documented(), self.entries())
+    }
+
+    /// Check that the percentage of documented attributes is at least
+    /// the given threshold.
+    pub fn check_threshold(&self, threshold: f64) -> Result<(), NixdocError> {
+        let coverage = self.percentage();
+        if coverage < threshold {
+            Err(NixdocError::Coverage { coverage, threshold })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of function arguments of all attributes.
+    pub fn args(&self) -> usize {
+        self.items.iter().map(|item| item.args).sum()
+    }
+
+    /// Number of documented function arguments.
+    pub fn documented_args(&self) -> usize {
+        self.items.iter().map(|item| item.documented_args).sum()
+    }
+
+    /// Percentage of documented function arguments.
+    pub fn args_percentage(&self) -> f64 {
+        percentage(self.documented_args(), self.args())
+    }
+
+    fn write_summary<W: Write>(&self, w: &mut W, label: &str) -> Result<(), Error> {
+        writeln!(w, "{}: {}/{} attributes documented ({:.1}%), {}/{} arguments documented ({:.1}%)",
+                 label,
+                 self.documented(), self.entries(), self.percentage(),
+                 self.documented_args(), self.args(), self.args_percentage())?;
+        Ok(())
+    }
+}
+
+/// Write a human-readable coverage report listing every exported
+/// attribute of the given categories, followed by the totals.
+pub fn write_coverage_report<W: Write>(w: &mut W, categories: &[Coverage]) -> Result<(), Error> {
+    for category in categories {
+        category.write_summary(w, &category.name)?;
+
+        for item in &category.items {
+            let marker = match item.status {
+                Status::Documented => "[x]",
+                Status::Undocumented => "[ ]",
+                Status::Alias => "[=]",
+            };
+            write!(w, "  {} {}", marker, item.name)?;
+
+            if !item.undocumented_args.is_empty() {
+                write!(w, " (undocumented arguments: {})", item.undocumented_args.join(", "))?;
+            }
+            writeln!(w)?;
+        }
+
+        writeln!(w)?;
+    }
+
+    Coverage::total(categories).write_summary(w, "total")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::EXIT_INSUFFICIENT_COVERAGE;
+    use DocComment;
+
+    fn item(name: &str, doc: &str, inherited: bool) -> DocItem {
+        DocItem {
+            name: name.into(),
+            comment: DocComment { doc: doc.into(), ..Default::default() },
+            args: vec![Argument::Flat(SingleArg { name: "x".into(), doc: None })],
+            inherited_from: if inherited { Some(vec!["builtins".into(), name.into()]) } else { None },
+            ..Default::default()
+        }
+    }
+
+    fn coverage(items: Vec<DocItem>) -> Coverage {
+        Coverage::of_category(&Category {
+            name: "strings".into(),
+            description: String::new(),
+            items,
+        })
+    }
+
+    #[test]
+    fn nothing_to_document() {
+        let coverage = coverage(vec![]);
+        assert_eq!(coverage.entries(), 0);
+        assert_eq!(coverage.percentage(), 100.0);
+        assert_eq!(coverage.args_percentage(), 100.0);
+    }
+
+    #[test]
+    fn aliases_do_not_count() {
+        let coverage = coverage(vec![
+            item("documented", "Documented.", false),
+            item("undocumented", "", false),
+            item("head", "", true),
+        ]);
+        assert_eq!(coverage.entries(), 2);
+        assert_eq!(coverage.documented(), 1);
+        assert_eq!(coverage.percentage(), 50.0);
+        assert_eq!((coverage.documented_args(), coverage.args()), (0, 3));
+    }
+
+    #[test]
+    fn threshold() {
+        let coverage = coverage(vec![item("documented", "Documented.", false), item("undocumented", "", false)]);
+        assert!(coverage.check_threshold(50.0).is_ok());
+
+        let error = coverage.check_threshold(80.0).unwrap_err();
+        assert_eq!(error.exit_code(), EXIT_INSUFFICIENT_COVERAGE);
+        assert_eq!(error.to_string(), "documentation coverage of 50.0% is below the required 80.0%");
+    }
+}
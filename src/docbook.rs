@@ -15,10 +15,17 @@
 
 //! This module implements DocBook XML output for a struct
 //! representing a single entry in th emanual.
+//!
+//! Descriptions are Markdown, which is mapped to the corresponding
+//! DocBook elements (e.g. `<literal>` for inline code).
 
 use std::io::Write;
 use xml::writer::{EventWriter, XmlEvent};
 use failure::Error;
+use pulldown_cmark::{Event, Parser, Tag};
+
+use RenderOptions;
+use types::split_type_names;
 
 /// Write a plain start element (most commonly used).
 fn element<W: Write>(w: &mut EventWriter<W>, name: &str) -> Result<(), Error> {
@@ -38,9 +45,163 @@ fn string<W: Write>(w: &mut EventWriter<W>, content: &str) -> Result<(), Error>
     Ok(())
 }
 
+/// Write Markdown text as DocBook block elements.
+///
+/// Inline content that is not wrapped in a paragraph by CommonMark
+/// (e.g. the items of tight lists) is wrapped in an implicit `<para>`,
+/// as DocBook does not allow text directly inside of block elements.
+fn markdown<W: Write>(w: &mut EventWriter<W>, text: &str) -> Result<(), Error> {
+    // Whether an implicit paragraph is currently open.
+    let mut implicit_para = false;
+
+    // Depth of the currently open elements that accept inline content.
+    let mut inline_depth = 0;
+
+    // Content of the code block that is currently being read, if any.
+    let mut code: Option<String> = None;
+
+    macro_rules! open_para {
+        () => {
+            if inline_depth == 0 && !implicit_para {
+                element(w, "para")?;
+                implicit_para = true;
+            }
+        }
+    }
+
+    macro_rules! close_para {
+        () => {
+            if implicit_para {
+                end(w)?;
+                implicit_para = false;
+            }
+        }
+    }
+
+    for event in Parser::new(text) {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                close_para!();
+                inline_depth += 1;
+                element(w, "para")?;
+            },
+
+            Event::Start(Tag::Header(level)) => {
+                close_para!();
+                inline_depth += 1;
+                w.write(XmlEvent::start_element("bridgehead")
+                        .attr("renderas", &format!("sect{}", level.min(5))))?;
+            },
+
+            Event::End(Tag::Paragraph) | Event::End(Tag::Header(_)) => {
+                inline_depth -= 1;
+                end(w)?;
+            },
+
+            Event::Start(Tag::CodeBlock(lang)) => {
+                close_para!();
+                if lang.is_empty() {
+                    element(w, "programlisting")?;
+                } else {
+                    w.write(XmlEvent::start_element("programlisting")
+                            .attr("language", &lang))?;
+                }
+                code = Some(String::new());
+            },
+
+            Event::End(Tag::CodeBlock(_)) => {
+                w.write(XmlEvent::cdata(code.take().unwrap_or_default().trim_end()))?;
+                end(w)?;
+            },
+
+            Event::Start(Tag::List(None)) => {
+                close_para!();
+                element(w, "itemizedlist")?;
+            },
+
+            Event::Start(Tag::List(Some(_))) => {
+                close_para!();
+                element(w, "orderedlist")?;
+            },
+
+            Event::Start(Tag::Item) => element(w, "listitem")?,
+
+            Event::Start(Tag::BlockQuote) => {
+                close_para!();
+                element(w, "blockquote")?;
+            },
+
+            Event::End(Tag::List(_)) | Event::End(Tag::Item) | Event::End(Tag::BlockQuote) => {
+                close_para!();
+                end(w)?;
+            },
+
+            Event::Start(Tag::Emphasis) => {
+                open_para!();
+                element(w, "emphasis")?;
+            },
+
+            Event::Start(Tag::Strong) => {
+                open_para!();
+                w.write(XmlEvent::start_element("emphasis").attr("role", "strong"))?;
+            },
+
+            Event::Start(Tag::Code) => {
+                open_para!();
+                element(w, "literal")?;
+            },
+
+            // Links to `#` lead nowhere, only their text is kept.
+            Event::Start(Tag::Link(ref url, _)) | Event::Start(Tag::Image(ref url, _)) if url.as_ref() == "#" => {
+                open_para!();
+            },
+
+            // Images can not be represented inline, they are linked
+            // to instead.
+            Event::Start(Tag::Link(url, _)) | Event::Start(Tag::Image(url, _)) => {
+                open_para!();
+                match url.strip_prefix('#') {
+                    Some(id) => w.write(XmlEvent::start_element("link").attr("linkend", id))?,
+                    None => w.write(XmlEvent::start_element("link").attr("xlink:href", &url))?,
+                }
+            },
+
+            Event::End(Tag::Link(ref url, _)) | Event::End(Tag::Image(ref url, _)) if url.as_ref() == "#" => (),
+
+            Event::End(Tag::Emphasis) | Event::End(Tag::Strong) | Event::End(Tag::Code)
+                | Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => end(w)?,
+
+            Event::Text(content) => match &mut code {
+                Some(code) => code.push_str(&content),
+                None => {
+                    open_para!();
+                    string(w, &content)?;
+                },
+            },
+
+            Event::Html(content) | Event::InlineHtml(content) => {
+                open_para!();
+                string(w, &content)?;
+            },
+
+            Event::SoftBreak | Event::HardBreak => string(w, "\n")?,
+
+            // Rules, tables and footnotes have no equivalent in the
+            // generated documentation, their content is kept as text.
+            _ => (),
+        }
+    }
+
+    if implicit_para {
+        end(w)?;
+    }
+
+    Ok(())
+}
+
 /// Represent a single function argument name and its (optional)
 /// doc-string.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SingleArg {
     pub name: String,
     pub doc: Option<String>,
@@ -48,7 +209,7 @@ pub struct SingleArg {
 
 /// Represent a function argument, which is either a flat identifier
 /// or a pattern set.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Argument {
     /// Flat function argument (e.g. `n: n * 2`).
     Flat(SingleArg),
@@ -111,8 +272,292 @@ impl Argument {
     }
 }
 
-/// Represents a single manual section describing a library function.
+/// A usage example, which may be named.
+#[derive(Debug, Clone)]
+pub struct Example {
+    /// Title of the example, if it has one.
+    pub title: Option<String>,
+
+    /// Code of the example.
+    pub code: String,
+}
+
+/// Kind of a part of an example.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentKind {
+    /// Code, e.g. a function call.
+    Input,
+
+    /// The result of evaluating the code preceding it, written after
+    /// `=>` in the example.
+    Result,
+}
+
+/// A part of an example.
+#[derive(Debug, Clone)]
+pub struct ExampleSegment {
+    pub kind: SegmentKind,
+
+    /// One-based line of the example on which the segment starts.
+    pub line: usize,
+
+    /// Text of the segment, without the `=>` of results.
+    pub text: String,
+}
+
+/// Where in the code of an example a `CodeScanner` is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScanState {
+    Code,
+    String,
+    IndentedString,
+    Comment,
+}
+
+/// Scanner for the lines of the code of an example, which keeps track
+/// of strings and comments across lines so that brackets and `=>` in
+/// them are ignored.
+#[derive(Debug, Clone, Copy)]
+struct CodeScanner {
+    state: ScanState,
+}
+
+fn is_identifier_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'\'' || c == b'-'
+}
+
+impl CodeScanner {
+    fn new() -> CodeScanner {
+        CodeScanner { state: ScanState::Code }
+    }
+
+    /// Scan a line, returning the net number of brackets opened in it.
+    /// If `stop_at_arrow` is set, scanning stops at the first `=>`
+    /// outside of strings and comments, whose position is returned.
+    fn scan(&mut self, line: &str, stop_at_arrow: bool) -> (i32, Option<usize>) {
+        let bytes = line.as_bytes();
+        let at = |i: usize| bytes.get(i).cloned().unwrap_or(0);
+        let mut balance = 0;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let c = bytes[i];
+            i += match self.state {
+                ScanState::Code => match c {
+                    b'=' if stop_at_arrow && at(i + 1) == b'>' => return (balance, Some(i)),
+                    b'#' => break,
+                    b'/' if at(i + 1) == b'*' => { self.state = ScanState::Comment; 2 },
+                    b'"' => { self.state = ScanState::String; 1 },
+                    // Quotes may also be part of identifiers, e.g. `f''`
+                    b'\'' if at(i + 1) == b'\'' && !(i > 0 && is_identifier_byte(bytes[i - 1])) => {
+                        self.state = ScanState::IndentedString;
+                        2
+                    },
+                    b'(' | b'[' | b'{' => { balance += 1; 1 },
+                    b')' | b']' | b'}' => { balance -= 1; 1 },
+                    _ => 1,
+                },
+                ScanState::String => match c {
+                    b'\\' => 2,
+                    b'"' => { self.state = ScanState::Code; 1 },
+                    _ => 1,
+                },
+                ScanState::IndentedString => match (c, at(i + 1), at(i + 2)) {
+                    // `'''` and `''$` are escaped, as is any character after `''\`
+                    (b'\'', b'\'', b'\'') | (b'\'', b'\'', b'$') => 3,
+                    (b'\'', b'\'', b'\\') => 4,
+                    (b'\'', b'\'', _) => { self.state = ScanState::Code; 2 },
+                    _ => 1,
+                },
+                ScanState::Comment => match c {
+                    b'*' if at(i + 1) == b'/' => { self.state = ScanState::Code; 2 },
+                    _ => 1,
+                },
+            };
+        }
+
+        (balance, None)
+    }
+}
+
+/// Split the code of an example into code and the results given
+/// after `=>` (on the same line as the code or on the following one).
+/// A result continues for as long as it has unclosed brackets, strings
+/// or comments. `=>` and brackets in strings and comments are ignored.
+pub fn split_example(code: &str) -> Vec<ExampleSegment> {
+    let mut segments: Vec<ExampleSegment> = vec![];
+    let mut input_scanner = CodeScanner::new();
+    let mut result_scanner = CodeScanner::new();
+    let mut balance = 0;
+
+    for (idx, line) in code.lines().enumerate() {
+        let number = idx + 1;
+
+        // Continuation of a multi-line result
+        if balance > 0 || result_scanner.state != ScanState::Code {
+            if let Some(segment) = segments.last_mut() {
+                segment.text.push('\n');
+                segment.text.push_str(line);
+                balance += result_scanner.scan(line, false).0;
+                continue;
+            }
+        }
+        balance = 0;
+
+        let (input, result) = match input_scanner.scan(line, true).1 {
+            Some(arrow) => (line[..arrow].trim_end(), Some(line[arrow + 2..].trim())),
+            None => (line, None),
+        };
+
+        if result.is_none() || !input.trim().is_empty() {
+            let extend = segments.last().map(|s| s.kind) == Some(SegmentKind::Input);
+            match (extend, segments.last_mut()) {
+                (true, Some(segment)) => {
+                    segment.text.push('\n');
+                    segment.text.push_str(input);
+                },
+                _ => segments.push(ExampleSegment {
+                    kind: SegmentKind::Input,
+                    line: number,
+                    text: input.to_string(),
+                }),
+            }
+        }
+
+        if let Some(result) = result {
+            result_scanner = CodeScanner::new();
+            balance = result_scanner.scan(result, false).0;
+            segments.push(ExampleSegment {
+                kind: SegmentKind::Result,
+                line: number,
+                text: result.to_string(),
+            });
+        }
+    }
+
+    // Blank lines around code are not part of it.
+    segments.retain(|segment| !segment.text.trim().is_empty());
+    for segment in &mut segments {
+        while matches!(segment.text.lines().next(), Some(l) if l.trim().is_empty()) {
+            let first = segment.text.find('\n').map_or(segment.text.len(), |i| i + 1);
+            segment.text.drain(..first);
+            segment.line += 1;
+        }
+
+        let trimmed = segment.text.trim_end().len();
+        segment.text.truncate(trimmed);
+    }
+
+    segments
+}
+
+impl Example {
+    /// Split the example into code and results, see `split_example`.
+    pub fn segments(&self) -> Vec<ExampleSegment> {
+        split_example(&self.code)
+    }
+
+    /// Whether the example contains results after `=>`.
+    pub fn has_results(&self) -> bool {
+        self.segments().iter().any(|s| s.kind == SegmentKind::Result)
+    }
+}
+
+/// Write an example with results as a `<screen>`, in which the code
+/// is `<userinput>` and the results are `<computeroutput>`.
+///
+/// Text is written between all elements, as the writer would
+/// otherwise indent them, which changes the content of the screen.
+fn write_example_screen<W: Write>(w: &mut EventWriter<W>, example: &Example) -> Result<(), Error> {
+    element(w, "screen")?;
+
+    for (idx, segment) in example.segments().iter().enumerate() {
+        match segment.kind {
+            SegmentKind::Input => {
+                string(w, if idx == 0 { "" } else { "\n" })?;
+                element(w, "userinput")?;
+            },
+            SegmentKind::Result => {
+                string(w, if idx == 0 { "=> " } else { "\n=> " })?;
+                element(w, "computeroutput")?;
+            },
+        }
+        string(w, &segment.text)?;
+        end(w)?;
+    }
+
+    string(w, "")?;
+    end(w)?;
+    Ok(())
+}
+
+/// Location of a definition in the source.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// Path of the file, as it was passed to nixdoc.
+    pub file: String,
+
+    /// One-based line number of the definition.
+    pub line: usize,
+
+    /// One-based column of the definition.
+    pub column: usize,
+}
+
+impl Location {
+    /// Human-readable form of the location, e.g. `lib/strings.nix:42`.
+    pub fn display(&self) -> String {
+        format!("{}:{}", self.file.trim_start_matches("./"), self.line)
+    }
+}
+
+/// Write a paragraph describing the location of a definition, linked
+/// to the given URL if there is one.
+fn location_para<W: Write>(w: &mut EventWriter<W>,
+                           location: &Location,
+                           url: Option<&str>,
+                           id: Option<&str>) -> Result<(), Error> {
+    match id {
+        Some(id) => w.write(XmlEvent::start_element("para").attr("xml:id", id))?,
+        None => element(w, "para")?,
+    }
+
+    string(w, "Located at ")?;
+    if let Some(url) = url {
+        w.write(XmlEvent::start_element("link").attr("xlink:href", url))?;
+    }
+
+    element(w, "literal")?;
+    string(w, &location.display())?;
+    end(w)?;
+
+    if url.is_some() {
+        end(w)?;
+    }
+
+    string(w, ".")?;
+    end(w)?;
+    Ok(())
+}
+
+/// Reference to the original definition of a re-exported attribute.
 #[derive(Debug)]
+pub struct Alias {
+    /// Name of the original definition, e.g. `lib.strings.concatMap`
+    /// or `builtins.head`.
+    pub name: String,
+
+    /// Category of the original definition, if it is part of the
+    /// manual.
+    pub category: Option<String>,
+
+    /// Identifier of the section documenting the original definition,
+    /// if it is part of the manual.
+    pub ident: Option<String>,
+}
+
+/// Represents a single manual section describing a library function.
+#[derive(Debug, Default)]
 pub struct ManualEntry {
     /// Name of the function category (e.g. 'strings', 'trivial', 'attrsets')
     pub category: String,
@@ -120,30 +565,109 @@ pub struct ManualEntry {
     /// Name of the section (used as the title)
     pub name: String,
 
+    /// Attribute path of the set containing the entry, relative to
+    /// the category (empty for top-level entries).
+    pub path: Vec<String>,
+
     /// Type signature (if provided). This is not actually a checked
     /// type signature in any way.
     pub fn_type: Option<String>,
 
-    /// Primary description of the entry. Each entry is written as a
-    /// separate paragraph.
-    pub description: Vec<String>,
+    /// Primary description of the entry, as Markdown.
+    pub description: String,
 
-    /// Usage example for the entry.
-    pub example: Option<String>,
+    /// Usage examples for the entry.
+    pub examples: Vec<Example>,
 
     /// Arguments of the function
     pub args: Vec<Argument>,
+
+    /// Whether the entry documents a private binding of the file
+    /// rather than an exported attribute.
+    pub internal: bool,
+
+    /// Original definition, if the entry is re-exported (e.g. with
+    /// `inherit`) from elsewhere.
+    pub alias: Option<Alias>,
+
+    /// Prefix of the fully qualified name of the entry, e.g. `lib.`.
+    pub title_prefix: String,
+
+    /// Prefix of the identifiers of sections, e.g. `function-library-`.
+    pub id_prefix: String,
+
+    /// Location of the definition in the source.
+    pub location: Option<Location>,
+
+    /// URL linking to the location of the definition, if configured.
+    pub location_url: Option<String>,
+
+    /// Entries of the attribute set documented by this entry (if it
+    /// is one), which are written as nested sections.
+    pub children: Vec<ManualEntry>,
 }
 
 impl ManualEntry {
+    /// Attribute path of the entry within its category, e.g.
+    /// `versions.major`.
+    fn attr_path(&self) -> String {
+        let mut attr_path = String::new();
+        for attr in &self.path {
+            attr_path.push_str(attr);
+            attr_path.push('.');
+        }
+        attr_path.push_str(&self.name);
+        attr_path
+    }
+
+    /// Fully qualified name of the entry, e.g. `lib.strings.concatStrings`.
+    /// Internal entries are not reachable through `lib` and are titled
+    /// with their plain name.
+    pub fn title(&self) -> String {
+        if self.internal {
+            self.attr_path()
+        } else {
+            format!("{}{}.{}", self.title_prefix, self.category, self.attr_path())
+        }
+    }
+
+    /// Identifier of the entry, which is its name qualified with the
+    /// title prefix (also for internal entries) with characters that
+    /// are invalid in XML identifiers replaced.
+    pub fn ident(&self) -> String {
+        let qualified = if self.internal {
+            format!("{}{}.internal.{}", self.title_prefix, self.category, self.attr_path())
+        } else {
+            format!("{}{}.{}", self.title_prefix, self.category, self.attr_path())
+        };
+
+        qualified.replace("'", "-prime")
+    }
+
+    /// Identifier of the section of the entry, e.g.
+    /// `function-library-lib.strings.concatStrings`.
+    pub fn anchor(&self) -> String {
+        format!("{}{}", self.id_prefix, self.ident())
+    }
+
+    /// Identifier of the section of the original definition of a
+    /// re-exported entry, if it is part of the manual.
+    pub fn alias_anchor(&self) -> Option<String> {
+        self.alias.as_ref()
+            .and_then(|alias| alias.ident.as_ref())
+            .map(|ident| format!("{}{}", self.id_prefix, ident))
+    }
+
     /// Write a single DocBook entry for a documented Nix function.
-    pub fn write_section_xml<W: Write>(self, w: &mut EventWriter<W>) -> Result<(), Error> {
-        let title = format!("lib.{}.{}", self.category, self.name);
-        let ident = format!("lib.{}.{}", self.category, self.name.replace("'", "-prime"));
+    pub fn write_section_xml<W: Write>(self,
+                                       w: &mut EventWriter<W>,
+                                       opts: &RenderOptions) -> Result<(), Error> {
+        let title = self.title();
+        let ident = self.ident();
 
         // <section ...
         w.write(XmlEvent::start_element("section")
-                .attr("xml:id", format!("function-library-{}", ident).as_str()))?;
+                .attr("xml:id", &self.anchor()))?;
 
         // <title> ...
         element(w, "title")?;
@@ -154,28 +678,59 @@ impl ManualEntry {
 
         // Write an include header that will load manually written
         // documentation for this function if required.
-        let override_path = format!("./overrides/{}.xml", ident);
+        let override_path = format!("{}/{}.xml", opts.override_dir.trim_end_matches('/'), ident);
         w.write(XmlEvent::start_element("xi:include")
                 .attr("href", &override_path))?;
         element(w, "xi:fallback")?;
 
-        // <subtitle> (type signature)
+        // <subtitle> (type signature), linking type names if configured
         if let Some(t) = &self.fn_type {
             element(w, "subtitle")?;
             element(w, "literal")?;
-            string(w, t)?;
+            for (text, is_name) in split_type_names(t) {
+                match &opts.type_url {
+                    Some(template) if is_name => {
+                        w.write(XmlEvent::start_element("link")
+                                .attr("xlink:href", &template.replace("{name}", text)))?;
+                        string(w, text)?;
+                        end(w)?;
+                    },
+                    _ => string(w, text)?,
+                }
+            }
             end(w)?;
             end(w)?;
         }
 
-        // Primary doc string
-        // TODO: Split paragraphs?
-        for paragraph in &self.description {
+        // Reference to the original definition of re-exported entries
+        if let Some(alias) = &self.alias {
             element(w, "para")?;
-            string(w, paragraph)?;
+            string(w, "Alias of ")?;
+
+            match self.alias_anchor() {
+                Some(anchor) => {
+                    w.write(XmlEvent::start_element("link")
+                            .attr("linkend", &anchor))?;
+                    element(w, "function")?;
+                    string(w, &alias.name)?;
+                    end(w)?;
+                    end(w)?;
+                },
+
+                None => {
+                    element(w, "literal")?;
+                    string(w, &alias.name)?;
+                    end(w)?;
+                },
+            }
+
+            string(w, ".")?;
             end(w)?;
         }
 
+        // Primary doc string
+        markdown(w, &self.description)?;
+
         // Function argument names
         if !self.args.is_empty() {
             element(w, "variablelist")?;
@@ -187,11 +742,8 @@ impl ManualEntry {
             end(w)?;
         }
 
-        // Example program listing (if applicable)
-        //
-        // TODO: In grhmc's version there are multiple (named)
-        // examples, how can this be achieved automatically?
-        if let Some(example) = &self.example {
+        // Example program listings (if applicable)
+        for example in &self.examples {
             element(w, "example")?;
 
             element(w, "title")?;
@@ -200,12 +752,19 @@ impl ManualEntry {
             string(w, title.as_str())?;
             end(w)?;
 
-            string(w, " usage example")?;
+            match &example.title {
+                Some(example_title) => string(w, &format!(": {}", example_title))?,
+                None => string(w, " usage example")?,
+            }
             end(w)?;
 
-            element(w, "programlisting")?;
-            w.write(XmlEvent::cdata(example))?;
-            end(w)?;
+            if example.has_results() {
+                write_example_screen(w, example)?;
+            } else {
+                element(w, "programlisting")?;
+                w.write(XmlEvent::cdata(&example.code))?;
+                end(w)?;
+            }
 
             end(w)?;
         }
@@ -214,12 +773,23 @@ impl ManualEntry {
         end(w)?;
         end(w)?;
 
-        // Include link to function location (location information is
-        // generated by a separate script in nixpkgs)
-        w.write(XmlEvent::start_element("xi:include")
-                .attr("href", "./locations.xml")
-                .attr("xpointer", &ident))?;
-        end(w)?;
+        // Link to the function location. Unless the URL is known, the
+        // location is included from a separate document (generated by
+        // `write_locations_xml` or a separate script in nixpkgs).
+        match (&self.location, &self.location_url) {
+            (Some(location), Some(url)) => location_para(w, location, Some(url), None)?,
+            _ => {
+                w.write(XmlEvent::start_element("xi:include")
+                        .attr("href", &opts.locations_include)
+                        .attr("xpointer", &ident))?;
+                end(w)?;
+            },
+        }
+
+        // Members of nested attribute sets
+        for child in self.children {
+            child.write_section_xml(w, opts)?;
+        }
 
         // </section>
         end(w)?;
@@ -227,3 +797,95 @@ impl ManualEntry {
         Ok(())
     }
 }
+
+/// Write the location paragraphs of the given entries (and their
+/// children), identified by the entries' identifiers. This is the
+/// content of the `locations.xml` document included by entries.
+pub fn write_locations_xml<W: Write>(w: &mut EventWriter<W>,
+                                     entries: &[ManualEntry]) -> Result<(), Error> {
+    for entry in entries {
+        if let Some(location) = &entry.location {
+            let url = entry.location_url.as_deref();
+            location_para(w, location, url, Some(&entry.ident()))?;
+        }
+
+        write_locations_xml(w, &entry.children)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn docbook(text: &str) -> String {
+        let mut out = vec![];
+        {
+            let mut w = EventWriter::new(&mut out);
+            w.write(XmlEvent::start_element("section")
+                    .default_ns("http://docbook.org/ns/docbook")
+                    .ns("xlink", "http://www.w3.org/1999/xlink")).unwrap();
+            markdown(&mut w, text).unwrap();
+            end(&mut w).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn empty_fragment_links() {
+        let xml = docbook("See [the top](#) and [strings](#sec-strings).");
+        assert!(!xml.contains("linkend=\"\""), "{}", xml);
+        assert!(xml.contains("See the top and <link linkend=\"sec-strings\">strings</link>."), "{}", xml);
+    }
+
+    fn split(code: &str) -> Vec<(SegmentKind, usize, String)> {
+        split_example(code).into_iter()
+            .map(|s| (s.kind, s.line, s.text))
+            .collect()
+    }
+
+    #[test]
+    fn results_on_the_same_and_next_line() {
+        assert_eq!(split("f 1 => 2\ng 3\n=> {\n  a = 1;\n}\n"), vec![
+            (SegmentKind::Input, 1, "f 1".into()),
+            (SegmentKind::Result, 1, "2".into()),
+            (SegmentKind::Input, 2, "g 3".into()),
+            (SegmentKind::Result, 3, "{\n  a = 1;\n}".into()),
+        ]);
+    }
+
+    #[test]
+    fn arrows_in_strings_and_comments() {
+        assert_eq!(split("f \"=>\" # => not a result\n=> \"a => b\""), vec![
+            (SegmentKind::Input, 1, "f \"=>\" # => not a result".into()),
+            (SegmentKind::Result, 2, "\"a => b\"".into()),
+        ]);
+        assert_eq!(split("f /* => */ ''\n  => ''' x\n''\n=> 1"), vec![
+            (SegmentKind::Input, 1, "f /* => */ ''\n  => ''' x\n''".into()),
+            (SegmentKind::Result, 4, "1".into()),
+        ]);
+    }
+
+    #[test]
+    fn brackets_in_indented_strings() {
+        assert_eq!(split("f x\n=> [ '']'' ''$]'' ]\ng"), vec![
+            (SegmentKind::Input, 1, "f x".into()),
+            (SegmentKind::Result, 2, "[ '']'' ''$]'' ]".into()),
+            (SegmentKind::Input, 3, "g".into()),
+        ]);
+        assert_eq!(split("f x\n=> ''\n  ) =>\n''\ng"), vec![
+            (SegmentKind::Input, 1, "f x".into()),
+            (SegmentKind::Result, 2, "''\n  ) =>\n''".into()),
+            (SegmentKind::Input, 5, "g".into()),
+        ]);
+    }
+
+    #[test]
+    fn quotes_in_identifiers() {
+        assert_eq!(split("f'' [ x ] => [ 1 ]"), vec![
+            (SegmentKind::Input, 1, "f'' [ x ]".into()),
+            (SegmentKind::Result, 1, "[ 1 ]".into()),
+        ]);
+    }
+}
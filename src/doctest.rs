@@ -0,0 +1,359 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module turns usage examples into tests. Examples in lib
+//! follow the convention
+//!
+//! ```text
+//! concatStrings ["foo" "bar"]
+//! => "foobar"
+//! ```
+//!
+//! (or `expr => result` on a single line), see `split_example`. Each
+//! such pair is written as a test case in the format of `lib.runTests`.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use failure::Error;
+use rnix;
+
+use docbook::{split_example, ExampleSegment, Location, SegmentKind};
+use {Category, DocItem};
+
+/// An `expr => result` pair of an example.
+#[derive(Debug, Clone)]
+pub struct ExamplePair {
+    /// One-based line of the example on which the expression starts.
+    pub line: usize,
+
+    /// The expression.
+    pub expr: String,
+
+    /// The expected result of the expression.
+    pub expected: String,
+
+    /// One-based line of the example on which the result starts.
+    pub expected_line: usize,
+}
+
+/// A test case generated from an example.
+#[derive(Debug, Clone)]
+pub struct DocTest {
+    /// Name of the test, e.g. `testStringsConcatStrings`.
+    pub name: String,
+
+    /// Fully qualified name of the documented function.
+    pub function: String,
+
+    /// Category of the documented function, whose attributes are in
+    /// scope of the test.
+    pub category: String,
+
+    pub expr: String,
+    pub expected: String,
+}
+
+/// A syntax error in an example.
+#[derive(Debug, Clone)]
+pub struct ExampleError {
+    /// Fully qualified name of the documented function.
+    pub function: String,
+
+    /// Location of the function's definition.
+    pub location: Option<Location>,
+
+    /// One-based line within the example at which the error occurs.
+    pub line: usize,
+
+    pub message: String,
+}
+
+impl fmt::Display for ExampleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location.display())?;
+        }
+        write!(f, "warning: {}: example line {}: {}", self.function, self.line, self.message)
+    }
+}
+
+/// Split the code of an example into its `expr => result` pairs.
+/// Code that is not followed by a result is ignored, as is code that
+/// is separated from its result by a blank line.
+pub fn example_pairs(code: &str) -> Vec<ExamplePair> {
+    let mut pairs = vec![];
+    let mut input: Option<ExampleSegment> = None;
+
+    for segment in split_example(code) {
+        if segment.kind == SegmentKind::Input {
+            input = Some(segment);
+            continue;
+        }
+
+        if let Some(input) = input.take() {
+            // Only the code following the last blank line belongs to
+            // the result.
+            let lines: Vec<&str> = input.text.lines().collect();
+            let start = lines.iter().rposition(|l| l.trim().is_empty()).map_or(0, |idx| idx + 1);
+
+            pairs.push(ExamplePair {
+                line: input.line + start,
+                expr: lines[start..].join("\n").trim().to_string(),
+                expected: segment.text,
+                expected_line: segment.line,
+            });
+        }
+    }
+
+    pairs
+}
+
+/// Check that a snippet is a valid Nix expression, returning the
+/// (one-based) line within the snippet and message of the error.
+pub fn check_syntax(code: &str) -> Result<(), (usize, String)> {
+    match rnix::parse(code) {
+        Ok(_) => Ok(()),
+        Err((span, err)) => {
+            let offset = span.map_or(code.len(), |s| (s.start as usize).min(code.len()));
+            let line = code[..offset].matches('\n').count() + 1;
+            Err((line, err.to_string()))
+        },
+    }
+}
+
+/// Check both sides of an example pair, see `check_syntax`.
+pub fn check_pair(pair: &ExamplePair) -> Result<(), (usize, String)> {
+    check_syntax(&pair.expr).map_err(|(line, message)| {
+        (pair.line + line - 1, format!("cannot parse expression: {}", message))
+    })?;
+
+    check_syntax(&pair.expected).map_err(|(line, message)| {
+        (pair.expected_line + line - 1, format!("cannot parse expected result: {}", message))
+    })
+}
+
+/// Name of a test case for a function, e.g. `testStringsConcatStrings`.
+/// Further examples of the function are numbered, e.g.
+/// `testStringsConcatStrings-example2`.
+fn test_name(category: &str, attr_path: &str, example: usize) -> String {
+    let mut name = String::from("test");
+    for part in Some(category).into_iter().chain(attr_path.split('.')) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.extend(chars);
+        }
+    }
+
+    if example > 1 {
+        name.push_str(&format!("-example{}", example));
+    }
+    name
+}
+
+/// Make a test name unique among the names already used by appending
+/// a number to it, e.g. `testStringsAB-2` if both `a.b` and `aB` are
+/// documented.
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut suffix = 1;
+    while used.contains(&unique) {
+        suffix += 1;
+        unique = format!("{}-{}", name, suffix);
+    }
+
+    used.insert(unique.clone());
+    unique
+}
+
+/// Whether a name can be used as an attribute name without quoting.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'' || c == '-')
+}
+
+/// Name of an item as it is referred to in messages, e.g.
+/// `lib.strings.concatStrings` with the title prefix `lib.`. Internal
+/// items are not reachable through `lib` and are referred to by their
+/// attribute path.
+fn item_name(category: &Category, item: &DocItem, title_prefix: &str) -> String {
+    if item.internal {
+        item.attr_path()
+    } else {
+        format!("{}{}.{}", title_prefix, category.name, item.attr_path())
+    }
+}
+
+/// Check the syntax of all examples of a category. Examples with
+/// `expr => result` pairs are checked pair by pair, other examples
+/// as a whole. Functions are named with the given title prefix.
+pub fn check_examples(category: &Category, title_prefix: &str) -> Vec<ExampleError> {
+    let mut errors = vec![];
+
+    for item in &category.items {
+        for example in &item.comment.examples {
+            let pairs = example_pairs(&example.code);
+            let results: Vec<Result<(), (usize, String)>> = if pairs.is_empty() {
+                vec![check_syntax(&example.code)
+                     .map_err(|(line, message)| (line, format!("cannot parse example: {}", message)))]
+            } else {
+                pairs.iter().map(check_pair).collect()
+            };
+
+            for (line, message) in results.into_iter().filter_map(Result::err) {
+                errors.push(ExampleError {
+                    function: item_name(category, item, title_prefix),
+                    location: item.location.clone(),
+                    line,
+                    message,
+                });
+            }
+        }
+    }
+
+    errors
+}
+
+/// Collect the test cases of all examples of the exported attributes
+/// of the given categories. Pairs that cannot be parsed are left out,
+/// they are reported by `check_examples`. Functions are named with the
+/// given title prefix.
+pub fn collect_doctests(categories: &[Category], title_prefix: &str) -> Vec<DocTest> {
+    let mut tests = vec![];
+    let mut used = HashSet::new();
+
+    for category in categories {
+        for item in category.items.iter().filter(|item| !item.internal) {
+            let mut count = 0;
+
+            for example in &item.comment.examples {
+                for pair in example_pairs(&example.code) {
+                    if check_pair(&pair).is_err() {
+                        continue;
+                    }
+
+                    count += 1;
+                    tests.push(DocTest {
+                        name: unique_name(test_name(&category.name, &item.attr_path(), count), &mut used),
+                        function: item_name(category, item, title_prefix),
+                        category: category.name.clone(),
+                        expr: pair.expr,
+                        expected: pair.expected,
+                    });
+                }
+            }
+        }
+    }
+
+    tests
+}
+
+/// Indent all but the first line of a snippet.
+fn indent(code: &str, prefix: &str) -> String {
+    code.lines().collect::<Vec<_>>().join(&format!("\n{}", prefix))
+}
+
+/// Write the test cases as a Nix file for `lib.runTests`, which
+/// evaluates to the list of failing tests. Each test is evaluated
+/// with `lib` and the category of its function in scope.
+pub fn write_doctests<W: Write>(w: &mut W, tests: &[DocTest]) -> Result<(), Error> {
+    writeln!(w, "# Do not edit this file manually! It was generated using nixdoc")?;
+    writeln!(w, "# from the examples in the documentation of lib.")?;
+    writeln!(w, "{{ lib ? import <nixpkgs/lib> }}:")?;
+    writeln!(w)?;
+    writeln!(w, "lib.runTests {{")?;
+
+    for test in tests {
+        let name = if is_identifier(&test.name) {
+            test.name.clone()
+        } else {
+            format!("\"{}\"", test.name.replace('\\', "\\\\").replace('"', "\\\"").replace("${", "\\${"))
+        };
+
+        writeln!(w, "  # {}", test.function)?;
+        writeln!(w, "  {} = with lib; with lib.{}; {{", name, test.category)?;
+        writeln!(w, "    expr = {};", indent(&test.expr, "      "))?;
+        writeln!(w, "    expected = {};", indent(&test.expected, "      "))?;
+        writeln!(w, "  }};")?;
+    }
+
+    writeln!(w, "}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(code: &str) -> Vec<(usize, String, String, usize)> {
+        example_pairs(code).into_iter()
+            .map(|pair| (pair.line, pair.expr, pair.expected, pair.expected_line))
+            .collect()
+    }
+
+    #[test]
+    fn single_line_pairs() {
+        assert_eq!(pairs("foo 1 => 2\nfoo 2 => 3"), vec![
+            (1, "foo 1".into(), "2".into(), 1),
+            (2, "foo 2".into(), "3".into(), 2),
+        ]);
+    }
+
+    #[test]
+    fn multi_line_pairs() {
+        assert_eq!(pairs("concatStrings [\n  \"a\"\n  \"b\"\n]\n=> \"ab\"\nsplitString \"/\" \"a/b\"\n=> [ \"a\"\n     \"b\" ]"), vec![
+            (1, "concatStrings [\n  \"a\"\n  \"b\"\n]".into(), "\"ab\"".into(), 5),
+            (6, "splitString \"/\" \"a/b\"".into(), "[ \"a\"\n     \"b\" ]".into(), 7),
+        ]);
+    }
+
+    #[test]
+    fn code_before_blank_line_is_not_part_of_the_pair() {
+        assert_eq!(pairs("x = 1;\n\nfoo x\n=> 2"), vec![(3, "foo x".into(), "2".into(), 4)]);
+    }
+
+    #[test]
+    fn code_without_result() {
+        assert!(pairs("foo 1\nbar 2").is_empty());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(test_name("strings", "concatStrings", 1), "testStringsConcatStrings");
+        assert_eq!(test_name("strings", "foo", 2), "testStringsFoo-example2");
+        assert_eq!(test_name("trivial", "versions.major", 1), "testTrivialVersionsMajor");
+        assert_eq!(test_name("strings", "foo'", 1), "testStringsFoo'");
+    }
+
+    #[test]
+    fn unique_names() {
+        let mut used = HashSet::new();
+        let names: Vec<String> = vec![("a.b", 1), ("aB", 1), ("foo", 2), ("foo2", 1), ("foo'", 1), ("foo_", 1)]
+            .into_iter()
+            .map(|(path, example)| unique_name(test_name("strings", path, example), &mut used))
+            .collect();
+
+        assert_eq!(names, vec!["testStringsAB", "testStringsAB-2", "testStringsFoo-example2",
+                               "testStringsFoo2", "testStringsFoo'", "testStringsFoo_"]);
+    }
+
+    #[test]
+    fn quoted_names() {
+        assert!(is_identifier("testStringsFoo'-example2"));
+        assert!(!is_identifier("testStringsFoo bar"));
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module defines the errors reported by nixdoc and how they
+//! are presented to the user.
+
+use std::io;
+use std::path::Path;
+
+/// Exit code used when the input (files or arguments) is invalid.
+/// This is `EX_DATAERR` from `sysexits.h`.
+pub const EXIT_BAD_INPUT: i32 = 65;
+
+/// Exit code used when the documentation coverage is below the
+/// required threshold.
+pub const EXIT_INSUFFICIENT_COVERAGE: i32 = 1;
+
+/// Exit code used when reading or writing files fails. This is
+/// `EX_IOERR` from `sysexits.h`.
+pub const EXIT_IO_FAILURE: i32 = 74;
+
+/// Errors that can occur while running nixdoc.
+#[derive(Debug, Fail)]
+pub enum NixdocError {
+    /// The command line arguments are inconsistent.
+    #[fail(display = "{}", _0)]
+    Usage(String),
+
+    /// An input file could not be parsed as Nix.
+    #[fail(display = "{}:{}:{}: {}\n{}", file, line, column, message, snippet)]
+    Parse {
+        file: String,
+        line: usize,
+        column: usize,
+        message: String,
+        snippet: String,
+    },
+
+    /// The configuration file is invalid.
+    #[fail(display = "invalid configuration in {}: {}", path, message)]
+    Config {
+        path: String,
+        message: String,
+    },
+
+    /// Reading an input file failed.
+    #[fail(display = "failed to read {}: {}", path, cause)]
+    Read {
+        path: String,
+        #[cause] cause: io::Error,
+    },
+
+    /// Writing an output document failed.
+    #[fail(display = "failed to write {}: {}", target, message)]
+    Write {
+        target: String,
+        message: String,
+    },
+
+    /// Examples in the documentation are not valid Nix, or type
+    /// signatures are malformed.
+    #[fail(display = "documentation check failed: {} invalid example(s), {} malformed type signature(s)",
+           examples, signatures)]
+    Check {
+        examples: usize,
+        signatures: usize,
+    },
+
+    /// The documentation does not match the definitions it documents,
+    /// see `--lint`.
+    #[fail(display = "documentation lint failed: {} warning(s)", lints)]
+    Lint {
+        lints: usize,
+    },
+
+    /// Less of the library is documented than required.
+    #[fail(display = "documentation coverage of {:.1}% is below the required {:.1}%", coverage, threshold)]
+    Coverage {
+        coverage: f64,
+        threshold: f64,
+    },
+}
+
+impl NixdocError {
+    /// Exit code of the process if this error occurs.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NixdocError::Usage(_)
+            | NixdocError::Config { .. }
+            | NixdocError::Parse { .. }
+            | NixdocError::Check { .. }
+            | NixdocError::Lint { .. } => EXIT_BAD_INPUT,
+            NixdocError::Read { .. } | NixdocError::Write { .. } => EXIT_IO_FAILURE,
+            NixdocError::Coverage { .. } => EXIT_INSUFFICIENT_COVERAGE,
+        }
+    }
+
+    /// Construct an error for a failed read of the given file.
+    pub fn read(path: &Path, cause: io::Error) -> NixdocError {
+        NixdocError::Read {
+            path: path.display().to_string(),
+            cause,
+        }
+    }
+
+    /// Construct a parse error at the given byte offset in `src`,
+    /// including a snippet of the offending line. Errors without a
+    /// known location (e.g. at the end of the file) are reported at
+    /// the end of the source.
+    pub fn parse(path: &Path, src: &str, offset: Option<usize>, message: String) -> NixdocError {
+        let offset = offset.unwrap_or(src.len()).min(src.len());
+        let (line, column) = line_column(src, offset);
+
+        NixdocError::Parse {
+            file: path.display().to_string(),
+            line,
+            column,
+            message,
+            snippet: snippet(src, line, column),
+        }
+    }
+}
+
+/// Compute the (one-based) line and column of a byte offset.
+pub fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let column = before[line_start..].chars().count() + 1;
+
+    (line, column)
+}
+
+/// Render the given line of the source with a marker pointing at the
+/// column, in the style of compiler diagnostics.
+fn snippet(src: &str, line: usize, column: usize) -> String {
+    let text = src.lines().nth(line - 1).unwrap_or("");
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+
+    format!("{} |\n{} | {}\n{} | {}^",
+            gutter, number, text, gutter, " ".repeat(column - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_and_last_lines() {
+        let src = "{\n  a = 1;\n  b = ;\n}";
+        assert_eq!(line_column(src, 0), (1, 1));
+        assert_eq!(line_column(src, src.find("b =").unwrap()), (3, 3));
+        assert_eq!(line_column(src, src.len() - 1), (4, 1));
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        let src = "{ ä = \"ö\"; b = ; }";
+        assert_eq!(line_column(src, src.find("b =").unwrap()), (1, 12));
+        assert_eq!(snippet(src, 1, 12), "  |\n1 | { ä = \"ö\"; b = ; }\n  |            ^");
+    }
+
+    #[test]
+    fn end_of_file() {
+        assert_eq!(line_column("{\n  a = 1;\n", 11), (3, 1));
+        assert_eq!(snippet("{\n  a = 1;\n", 3, 1), "  |\n3 | \n  | ^");
+
+        let error = NixdocError::parse(Path::new("x.nix"), "{ a = 1;", None, "unexpected end of file".into());
+        match error {
+            NixdocError::Parse { line, column, .. } => assert_eq!((line, column), (1, 9)),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn line_numbers_widen_the_gutter() {
+        let src = "x\n".repeat(11);
+        assert_eq!(snippet(&src, 10, 1), "   |\n10 | x\n   | ^");
+    }
+}
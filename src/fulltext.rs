@@ -0,0 +1,255 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module builds a static full-text search index of the manual
+//! entries, which lets generated documentation offer client-side
+//! search without a server.
+//!
+//! The index is an inverted index from terms to weighted document
+//! references, the format is documented in the README. Weights are
+//! computed up front, so that a client only has to sum the weights of
+//! the query terms per document.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use failure::Error;
+use serde_json;
+
+use docbook::{Argument, ManualEntry};
+use {manual_entries, Category, RenderOptions};
+
+/// Version of the search index format. This must be incremented
+/// whenever a field is removed or changes its meaning.
+pub const SEARCH_INDEX_VERSION: u32 = 1;
+
+/// Weight of terms occurring in the name of an entry.
+const WEIGHT_NAME: u32 = 10;
+
+/// Weight of terms occurring in the type signature.
+const WEIGHT_TYPE: u32 = 3;
+
+/// Weight of terms occurring in the argument names or their docs.
+const WEIGHT_ARGUMENTS: u32 = 3;
+
+/// Weight of terms occurring in the description.
+const WEIGHT_DESCRIPTION: u32 = 2;
+
+/// Weight of terms occurring in the examples.
+const WEIGHT_EXAMPLES: u32 = 1;
+
+/// A document that search results refer to.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchDocument {
+    /// Fully qualified name of the entry, e.g. `lib.strings.concatStrings`.
+    pub name: String,
+
+    /// Identifier of the entry's section, i.e. the anchor to link to.
+    pub anchor: String,
+
+    /// Category containing the entry.
+    pub category: String,
+
+    /// Type signature of the entry.
+    #[serde(rename = "type")]
+    pub doc_type: Option<String>,
+
+    /// First paragraph of the description, for displaying results.
+    pub summary: String,
+}
+
+/// The search index document.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchIndex {
+    pub version: u32,
+    pub documents: Vec<SearchDocument>,
+
+    /// Map from terms to the weights of the documents (by their index
+    /// in `documents`) containing them.
+    pub terms: BTreeMap<String, BTreeMap<usize, u32>>,
+}
+
+/// Split text into lowercase search terms. Identifiers written in
+/// camel case are indexed both as a whole and as their parts, so that
+/// `concatStrings` is found by `concat` and by `strings`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = vec![];
+
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if word.chars().count() < 2 {
+            continue;
+        }
+
+        terms.push(word.to_lowercase());
+
+        let mut part = String::new();
+        let mut parts = vec![];
+        for c in word.chars() {
+            if c.is_uppercase() && !part.is_empty() {
+                parts.push(part.to_lowercase());
+                part.clear();
+            }
+            part.push(c);
+        }
+        parts.push(part.to_lowercase());
+
+        if parts.len() > 1 {
+            terms.extend(parts.into_iter().filter(|p| p.chars().count() >= 2));
+        }
+    }
+
+    terms
+}
+
+/// First paragraph of a Markdown description.
+fn summary(description: &str) -> String {
+    description.split("\n\n")
+        .next()
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl SearchIndex {
+    /// Build the index over all manual entries of the categories.
+    pub fn build(categories: &[Category], opts: &RenderOptions) -> SearchIndex {
+        let mut index = SearchIndex {
+            version: SEARCH_INDEX_VERSION,
+            documents: vec![],
+            terms: BTreeMap::new(),
+        };
+
+        for category in categories {
+            for entry in manual_entries(&category.name, category.items.clone(), opts) {
+                index.add_entry(&entry);
+            }
+        }
+
+        index
+    }
+
+    fn add_terms(&mut self, document: usize, text: &str, weight: u32) {
+        for term in tokenize(text) {
+            *self.terms.entry(term)
+                .or_default()
+                .entry(document)
+                .or_insert(0) += weight;
+        }
+    }
+
+    fn add_entry(&mut self, entry: &ManualEntry) {
+        let document = self.documents.len();
+        self.documents.push(SearchDocument {
+            name: entry.title(),
+            anchor: entry.anchor(),
+            category: entry.category.clone(),
+            doc_type: entry.fn_type.clone(),
+            summary: summary(&entry.description),
+        });
+
+        // The title prefix is shared by all entries and would match
+        // every document.
+        let title = entry.title();
+        self.add_terms(document, title.strip_prefix(entry.title_prefix.as_str()).unwrap_or(&title), WEIGHT_NAME);
+        if let Some(doc_type) = &entry.fn_type {
+            self.add_terms(document, doc_type, WEIGHT_TYPE);
+        }
+        self.add_terms(document, &entry.description, WEIGHT_DESCRIPTION);
+
+        for arg in &entry.args {
+            let single_args = match arg {
+                Argument::Flat(single) => vec![single],
+                Argument::Pattern(pattern) => pattern.iter().collect(),
+            };
+
+            for single in single_args {
+                self.add_terms(document, &single.name, WEIGHT_ARGUMENTS);
+                if let Some(doc) = &single.doc {
+                    self.add_terms(document, doc, WEIGHT_ARGUMENTS);
+                }
+            }
+        }
+
+        for example in &entry.examples {
+            if let Some(title) = &example.title {
+                self.add_terms(document, title, WEIGHT_EXAMPLES);
+            }
+            self.add_terms(document, &example.code, WEIGHT_EXAMPLES);
+        }
+
+        for child in &entry.children {
+            self.add_entry(child);
+        }
+    }
+
+    /// Write the index as JSON.
+    pub fn write_json<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        serde_json::to_writer(&mut *w, self)?;
+        writeln!(w)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use docbook::Example;
+    use {DocComment, DocItem};
+
+    #[test]
+    fn camel_case_terms() {
+        assert_eq!(tokenize("concatStrings, a toJSON"), vec!["concatstrings", "concat", "strings", "tojson", "to"]);
+        assert_eq!(tokenize("lib.strings.x"), vec!["lib", "strings"]);
+    }
+
+    #[test]
+    fn summaries() {
+        assert_eq!(summary("Concatenate a\n  list of strings.\n\nMore details."), "Concatenate a list of strings.");
+        assert_eq!(summary(""), "");
+    }
+
+    #[test]
+    fn index_layout() {
+        let item = DocItem {
+            name: "concatStrings".into(),
+            comment: DocComment {
+                doc: "Concatenate a list of strings.".into(),
+                doc_type: Some("[string] -> string".into()),
+                examples: vec![Example { title: None, code: "concatStrings [ ]".into() }],
+            },
+            ..Default::default()
+        };
+        let categories = vec![Category { name: "strings".into(), description: String::new(), items: vec![item] }];
+
+        let mut out = vec![];
+        SearchIndex::build(&categories, &RenderOptions::default()).write_json(&mut out).unwrap();
+        let index: Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(index["version"], 1);
+        assert_eq!(index["documents"], json!([{
+            "name": "lib.strings.concatStrings",
+            "anchor": "function-library-lib.strings.concatStrings",
+            "category": "strings",
+            "type": "[string] -> string",
+            "summary": "Concatenate a list of strings.",
+        }]));
+        assert_eq!(index["terms"]["concat"], json!({ "0": 11 }));
+        assert_eq!(index["terms"]["string"], json!({ "0": 6 }));
+        assert_eq!(index["terms"]["strings"], json!({ "0": 23 }));
+        assert!(index["terms"].get("lib").is_none(), "{}", index["terms"]);
+    }
+}
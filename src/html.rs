@@ -0,0 +1,412 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements HTML output for the manual entries, which
+//! does not need a DocBook toolchain to be viewed.
+//!
+//! Pages are self-contained (styles are inlined, nothing is loaded
+//! from the network) and use the same identifiers as the DocBook
+//! output. A page contains one or more categories; links to entries
+//! of categories that are not on the current page point to the page
+//! `<category>.html`, as written with `--output-dir`.
+
+use std::borrow::Cow;
+use std::io::Write;
+use failure::Error;
+use pulldown_cmark::{html, Event, Parser, Tag};
+
+use docbook::{Argument, ManualEntry, SegmentKind};
+use types::split_type_names;
+
+/// Style sheet included in every page.
+const STYLE: &str = "
+body { font-family: sans-serif; line-height: 1.5; margin: 0; display: flex; }
+nav { min-width: 14em; padding: 1em; background: #f4f4f4; min-height: 100vh; }
+nav ul { list-style: none; padding-left: 0; }
+main { max-width: 50em; padding: 1em 2em; }
+pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }
+section { margin-bottom: 1.5em; }
+section section { margin-left: 1em; }
+pre.result { margin-top: -0.5em; background: none; border-left: 3px solid #f4f4f4; }
+pre.result::before { content: '=> '; color: #555; }
+.type { font-family: monospace; }
+.location { font-size: 0.9em; color: #555; }
+";
+
+/// Escape text for use in HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Categories of the library and those on the current page, which
+/// determine where links to entries point to.
+#[derive(Debug, Clone)]
+pub struct Links<'a> {
+    /// Names of all categories of the library.
+    pub all: &'a [String],
+
+    /// Names of the categories on the current page.
+    pub page: Vec<String>,
+
+    /// Prefix of the identifiers of entries' sections.
+    pub id_prefix: &'a str,
+
+    /// Prefix of the fully qualified names of entries, which is part
+    /// of their identifiers.
+    pub title_prefix: &'a str,
+
+    /// URL template for links to the documentation of type names.
+    pub type_url: Option<&'a str>,
+}
+
+impl<'a> Links<'a> {
+    /// Whether the page is part of a site with one page per category,
+    /// rather than a single page containing all of them.
+    fn is_site(&self) -> bool {
+        self.all.iter().any(|c| !self.page.contains(c))
+    }
+
+    /// Link to an element of the given category.
+    fn href(&self, category: &str, id: &str) -> String {
+        if self.page.iter().any(|c| c == category) {
+            format!("#{}", id)
+        } else {
+            format!("{}.html#{}", category, id)
+        }
+    }
+
+    /// Link to the section of the entry of the given category with the
+    /// given identifier, e.g. `lib.strings.concatStrings`.
+    fn entry_href(&self, category: &str, ident: &str) -> String {
+        self.href(category, &format!("{}{}", self.id_prefix, ident))
+    }
+
+    /// Rewrite links to anchors of other categories in descriptions,
+    /// which are written relative to a single combined document.
+    fn rewrite(&self, url: &str) -> String {
+        let entry_prefix = format!("#{}{}", self.id_prefix, self.title_prefix);
+        if url.starts_with(&entry_prefix) {
+            // The category is the longest one that the identifier
+            // starts with, as category names may contain dots.
+            let rest = &url[entry_prefix.len()..];
+            let category = self.all.iter()
+                .filter(|c| rest.starts_with(c.as_str()) && rest[c.len()..].starts_with('.'))
+                .max_by_key(|c| c.len());
+
+            if let Some(category) = category {
+                return self.href(category, &url[1..]);
+            }
+        }
+
+        if url.starts_with("#sec-functions-library-") {
+            let id = &url[1..];
+            let category = id["sec-functions-library-".len()..].trim_end_matches("-internal");
+            return self.href(category, id);
+        }
+
+        url.to_string()
+    }
+}
+
+/// Whether a URL may be used in a link or image, which is the case for
+/// relative URLs and those with the `http` or `https` scheme. Other
+/// schemes (e.g. `javascript:`) could run code in the page.
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_lowercase();
+    match url.find([':', '/', '?', '#']) {
+        Some(idx) if url[idx..].starts_with(':') => {
+            let scheme = &url[..idx];
+            scheme == "http" || scheme == "https"
+        },
+        _ => true,
+    }
+}
+
+/// Render Markdown as HTML, adjusting links to other categories.
+///
+/// Raw HTML in the Markdown is escaped, and links and images with
+/// unsafe URLs (see `is_safe_url`) are reduced to their text.
+fn markdown(text: &str, links: &Links) -> String {
+    let events = Parser::new(text).filter_map(|event| match event {
+        Event::Start(Tag::Link(ref url, _)) | Event::End(Tag::Link(ref url, _))
+        | Event::Start(Tag::Image(ref url, _)) | Event::End(Tag::Image(ref url, _))
+            if !is_safe_url(url) => None,
+        Event::Start(Tag::Link(url, title)) => {
+            Some(Event::Start(Tag::Link(Cow::from(links.rewrite(&url)), title)))
+        },
+        Event::End(Tag::Link(url, title)) => {
+            Some(Event::End(Tag::Link(Cow::from(links.rewrite(&url)), title)))
+        },
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        other => Some(other),
+    });
+
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Write the start of a page, including the navigation between the
+/// categories of the library.
+pub fn write_page_start<W: Write>(w: &mut W, title: &str, links: &Links) -> Result<(), Error> {
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<!-- Do not edit this file manually! It was generated using nixdoc. -->")?;
+    writeln!(w, "<html>")?;
+    writeln!(w, "<head>")?;
+    writeln!(w, "<meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>{}</title>", escape(title))?;
+    writeln!(w, "<style>{}</style>", STYLE)?;
+    writeln!(w, "</head>")?;
+    writeln!(w, "<body>")?;
+
+    writeln!(w, "<nav>")?;
+    if links.is_site() {
+        writeln!(w, "<p><a href=\"index.html\">Library functions</a></p>")?;
+    }
+    writeln!(w, "<ul>")?;
+    for category in links.all {
+        let id = format!("sec-functions-library-{}", category);
+        writeln!(w, "<li><a href=\"{}\">{}</a></li>",
+                 escape(&links.href(category, &id)), escape(category))?;
+    }
+    writeln!(w, "</ul>")?;
+    writeln!(w, "</nav>")?;
+
+    writeln!(w, "<main>")?;
+    Ok(())
+}
+
+/// Write the end of a page.
+pub fn write_page_end<W: Write>(w: &mut W) -> Result<(), Error> {
+    writeln!(w, "</main>")?;
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")?;
+    Ok(())
+}
+
+/// Write the index page of a site, listing all categories named with
+/// the title prefix.
+pub fn write_index_html<W: Write>(w: &mut W,
+                                  categories: &[(String, String)],
+                                  links: &Links) -> Result<(), Error> {
+    write_page_start(w, "Library functions", links)?;
+    writeln!(w, "<h1>Library functions</h1>")?;
+    writeln!(w, "<ul>")?;
+    for (name, description) in categories {
+        writeln!(w, "<li><a href=\"{}.html\"><code>{}{}</code></a>: {}</li>",
+                 escape(name), escape(links.title_prefix), escape(name), escape(description))?;
+    }
+    writeln!(w, "</ul>")?;
+    write_page_end(w)
+}
+
+/// Write the start of the section of a category, which has to be
+/// closed with `write_section_end`.
+pub fn write_category_html<W: Write>(w: &mut W,
+                                     category: &str,
+                                     description: &str) -> Result<(), Error> {
+    writeln!(w, "<section id=\"sec-functions-library-{}\">", escape(category))?;
+    writeln!(w, "<h1>{}</h1>", escape(description))?;
+    Ok(())
+}
+
+/// Write the start of the section containing the internal entries
+/// of a category, which has to be closed with `write_section_end`.
+pub fn write_internal_html<W: Write>(w: &mut W, category: &str) -> Result<(), Error> {
+    writeln!(w, "<section id=\"sec-functions-library-{}-internal\">", escape(category))?;
+    writeln!(w, "<h2>Internal functions</h2>")?;
+    Ok(())
+}
+
+/// Close a section.
+pub fn write_section_end<W: Write>(w: &mut W) -> Result<(), Error> {
+    writeln!(w, "</section>")?;
+    Ok(())
+}
+
+impl Argument {
+    /// Write a definition list entry for a single function argument.
+    fn write_argument_html<W: Write>(self, w: &mut W) -> Result<(), Error> {
+        match self {
+            Argument::Flat(arg) => {
+                let doc = arg.doc.unwrap_or("Function argument".into());
+                writeln!(w, "<dt><code>{}</code></dt>", escape(&arg.name))?;
+                writeln!(w, "<dd>{}</dd>", escape(doc.trim()))?;
+            },
+
+            Argument::Pattern(pattern_args) => {
+                writeln!(w, "<dt><code>pattern</code></dt>")?;
+                writeln!(w, "<dd>Structured function argument")?;
+                writeln!(w, "<dl>")?;
+                for pattern_arg in pattern_args {
+                    Argument::Flat(pattern_arg).write_argument_html(w)?;
+                }
+                writeln!(w, "</dl>")?;
+                writeln!(w, "</dd>")?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+impl ManualEntry {
+    /// Write a single HTML section for a documented Nix function.
+    pub fn write_section_html<W: Write>(self, w: &mut W, links: &Links) -> Result<(), Error> {
+        let title = self.title();
+        let level = (2 + self.path.len() + self.internal as usize).min(6);
+
+        writeln!(w, "<section id=\"{}\">", escape(&self.anchor()))?;
+        writeln!(w, "<h{0}><code>{1}</code></h{0}>", level, escape(&title))?;
+
+        // Type signature, linking type names if configured
+        if let Some(t) = &self.fn_type {
+            write!(w, "<p class=\"type\">")?;
+            for (text, is_name) in split_type_names(t) {
+                match links.type_url {
+                    Some(template) if is_name => write!(w, "<a href=\"{}\">{}</a>",
+                                                        escape(&template.replace("{name}", text)), escape(text))?,
+                    _ => write!(w, "{}", escape(text))?,
+                }
+            }
+            writeln!(w, "</p>")?;
+        }
+
+        // Reference to the original definition of re-exported entries
+        if let Some(alias) = &self.alias {
+            match (&alias.category, &alias.ident) {
+                (Some(category), Some(ident)) => {
+                    writeln!(w, "<p>Alias of <a href=\"{}\"><code>{}</code></a>.</p>",
+                             escape(&links.entry_href(category, ident)), escape(&alias.name))?
+                },
+                _ => writeln!(w, "<p>Alias of <code>{}</code>.</p>", escape(&alias.name))?,
+            }
+        }
+
+        // Primary doc string
+        if !self.description.is_empty() {
+            write!(w, "{}", markdown(&self.description, links))?;
+        }
+
+        // Function argument names
+        if !self.args.is_empty() {
+            writeln!(w, "<p>Arguments:</p>")?;
+            writeln!(w, "<dl>")?;
+            for arg in self.args {
+                arg.write_argument_html(w)?;
+            }
+            writeln!(w, "</dl>")?;
+        }
+
+        // Example program listings (if applicable)
+        for example in &self.examples {
+            match &example.title {
+                Some(example_title) => writeln!(w, "<p><code>{}</code>: {}</p>",
+                                                escape(&title), escape(example_title))?,
+                None => writeln!(w, "<p><code>{}</code> usage example</p>", escape(&title))?,
+            }
+            if example.has_results() {
+                for segment in example.segments() {
+                    match segment.kind {
+                        SegmentKind::Input => writeln!(w, "<pre><code class=\"language-nix\">{}</code></pre>",
+                                                       escape(&segment.text))?,
+                        SegmentKind::Result => writeln!(w, "<pre class=\"result\"><samp>{}</samp></pre>",
+                                                        escape(&segment.text))?,
+                    }
+                }
+            } else {
+                writeln!(w, "<pre><code class=\"language-nix\">{}</code></pre>",
+                         escape(example.code.trim_end()))?;
+            }
+        }
+
+        // Link to the function location
+        if let Some(location) = &self.location {
+            match &self.location_url {
+                Some(url) => writeln!(w, "<p class=\"location\">Located at <a href=\"{}\"><code>{}</code></a>.</p>",
+                                      escape(url), escape(&location.display()))?,
+                None => writeln!(w, "<p class=\"location\">Located at <code>{}</code>.</p>",
+                                 escape(&location.display()))?,
+            }
+        }
+
+        // Members of nested attribute sets
+        for child in self.children {
+            child.write_section_html(w, links)?;
+        }
+
+        writeln!(w, "</section>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(all: &[String]) -> Links<'_> {
+        Links {
+            all,
+            page: vec!["strings".into()],
+            id_prefix: "function-library-",
+            title_prefix: "lib.",
+            type_url: None,
+        }
+    }
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let all = vec!["strings".to_string()];
+        let html = markdown("<script>alert(1)</script>\n\nSee <b>this</b>.", &links(&all));
+        assert!(!html.contains("<script>"), "{}", html);
+        assert!(!html.contains("<b>"), "{}", html);
+        assert!(html.contains("&lt;script&gt;"), "{}", html);
+    }
+
+    #[test]
+    fn unsafe_links_are_dropped() {
+        let all = vec!["strings".to_string()];
+        let html = markdown("[a](javascript:alert(1)) [b](https://nixos.org) [c](foo.html) ![d](data:x)",
+                            &links(&all));
+        assert!(!html.contains("javascript"), "{}", html);
+        assert!(!html.contains("data:"), "{}", html);
+        assert!(html.contains("<a href=\"https://nixos.org\">b</a>"), "{}", html);
+        assert!(html.contains("<a href=\"foo.html\">c</a>"), "{}", html);
+        assert!(html.contains("a "), "{}", html);
+    }
+
+    #[test]
+    fn entry_links() {
+        let all = vec!["strings".to_string(), "lists".to_string(), "lists.sub".to_string()];
+        let links = links(&all);
+        assert_eq!(links.entry_href("lists", "lib.lists.internal.go"),
+                   "lists.html#function-library-lib.lists.internal.go");
+        assert_eq!(links.rewrite("#function-library-lib.strings.internal.go"),
+                   "#function-library-lib.strings.internal.go");
+        assert_eq!(links.rewrite("#function-library-lib.lists.sub.x"),
+                   "lists.sub.html#function-library-lib.lists.sub.x");
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements the machine-readable JSON export of the
+//! extracted documentation.
+//!
+//! The structures in here are deliberately separate from the ones
+//! used internally so that the output schema only changes when
+//! `SCHEMA_VERSION` is bumped. The schema itself is documented in
+//! the README.
+
+use std::io::Write;
+use failure::Error;
+use serde_json;
+
+use docbook::{Argument, SegmentKind, SingleArg};
+use types::Signature;
+use {Category, DocItem};
+
+/// Version of the JSON output schema. This must be incremented
+/// whenever a field is removed or changes its meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Top-level JSON document, which contains one or more categories.
+#[derive(Debug, Serialize)]
+pub struct JsonLibrary<'a> {
+    pub version: u32,
+    pub categories: Vec<JsonCategory<'a>>,
+}
+
+/// A single function category.
+#[derive(Debug, Serialize)]
+pub struct JsonCategory<'a> {
+    pub category: &'a str,
+    pub description: &'a str,
+    pub entries: Vec<JsonEntry<'a>>,
+}
+
+/// A single documented attribute.
+#[derive(Debug, Serialize)]
+pub struct JsonEntry<'a> {
+    pub name: &'a str,
+    pub path: &'a [String],
+    pub doc: &'a str,
+    #[serde(rename = "type")]
+    pub doc_type: Option<&'a str>,
+    pub signature: Option<Signature>,
+    pub examples: Vec<JsonExample<'a>>,
+    pub args: Vec<JsonArgument<'a>>,
+    pub internal: bool,
+    pub inherited_from: Option<&'a [String]>,
+    pub location: Option<JsonLocation<'a>>,
+}
+
+/// Location of a definition in its file.
+#[derive(Debug, Serialize)]
+pub struct JsonLocation<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A usage example.
+#[derive(Debug, Serialize)]
+pub struct JsonExample<'a> {
+    pub title: Option<&'a str>,
+    pub code: &'a str,
+    pub segments: Vec<JsonSegment>,
+}
+
+/// A part of a usage example, see `docbook::split_example`.
+#[derive(Debug, Serialize)]
+pub struct JsonSegment {
+    pub kind: &'static str,
+    pub code: String,
+}
+
+/// A function argument, tagged by its kind.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JsonArgument<'a> {
+    Flat {
+        name: &'a str,
+        doc: Option<&'a str>,
+    },
+
+    Pattern {
+        args: Vec<JsonArgument<'a>>,
+    },
+}
+
+impl<'a> JsonArgument<'a> {
+    fn from_single(arg: &'a SingleArg) -> JsonArgument<'a> {
+        JsonArgument::Flat {
+            name: &arg.name,
+            doc: arg.doc.as_ref().map(|d| d.trim()),
+        }
+    }
+
+    fn from_argument(arg: &'a Argument) -> JsonArgument<'a> {
+        match arg {
+            Argument::Flat(single) => JsonArgument::from_single(single),
+            Argument::Pattern(args) => JsonArgument::Pattern {
+                args: args.iter().map(JsonArgument::from_single).collect(),
+            },
+        }
+    }
+}
+
+impl<'a> JsonEntry<'a> {
+    fn from_item(item: &'a DocItem) -> JsonEntry<'a> {
+        JsonEntry {
+            name: &item.name,
+            path: &item.path,
+            doc: &item.comment.doc,
+            doc_type: item.comment.doc_type.as_deref(),
+            signature: item.comment.signature().and_then(Result::ok),
+            examples: item.comment.examples.iter()
+                .map(|example| JsonExample {
+                    title: example.title.as_deref(),
+                    code: &example.code,
+                    segments: example.segments().into_iter()
+                        .map(|segment| JsonSegment {
+                            kind: match segment.kind {
+                                SegmentKind::Input => "input",
+                                SegmentKind::Result => "result",
+                            },
+                            code: segment.text,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            args: item.args.iter().map(JsonArgument::from_argument).collect(),
+            internal: item.internal,
+            inherited_from: item.inherited_from.as_deref(),
+            location: item.location.as_ref().map(|location| JsonLocation {
+                file: &location.file,
+                line: location.line,
+                column: location.column,
+            }),
+        }
+    }
+}
+
+impl<'a> JsonCategory<'a> {
+    fn from_category(category: &'a Category) -> JsonCategory<'a> {
+        JsonCategory {
+            category: &category.name,
+            description: &category.description,
+            entries: category.items.iter().map(JsonEntry::from_item).collect(),
+        }
+    }
+}
+
+/// Write all documentation items of the given categories as a JSON
+/// document. The document has the same shape regardless of the number
+/// of categories.
+pub fn write_library_json<W: Write>(w: &mut W, categories: &[Category]) -> Result<(), Error> {
+    let library = JsonLibrary {
+        version: SCHEMA_VERSION,
+        categories: categories.iter().map(JsonCategory::from_category).collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut *w, &library)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use docbook::{Example, Location};
+    use DocComment;
+
+    #[test]
+    fn category() {
+        let item = DocItem {
+            name: "concat".into(),
+            comment: DocComment {
+                doc: "Concatenate strings.".into(),
+                doc_type: Some("concat :: [string] -> string".into()),
+                examples: vec![Example { title: None, code: "concat [ \"a\" ]\n=> \"a\"".into() }],
+            },
+            location: Some(Location { file: "lib/strings.nix".into(), line: 3, column: 5 }),
+            ..Default::default()
+        };
+        let categories = vec![Category {
+            name: "strings".into(),
+            description: "String functions".into(),
+            items: vec![item],
+        }];
+
+        let mut out = vec![];
+        write_library_json(&mut out, &categories).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(json["version"], 1);
+        let entry = &json["categories"][0]["entries"][0];
+        assert_eq!(entry["name"], "concat");
+        assert_eq!(entry["type"], "concat :: [string] -> string");
+        assert_eq!(entry["examples"][0]["segments"], json!([
+            { "kind": "input", "code": "concat [ \"a\" ]" },
+            { "kind": "result", "code": "\"a\"" },
+        ]));
+        assert_eq!(entry["location"], json!({ "file": "lib/strings.nix", "line": 3, "column": 5 }));
+    }
+}
@@ -0,0 +1,1652 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! nixdoc extracts documentation from Nix files defining library
+//! functions, such as the files in `lib/` in the nixpkgs repository,
+//! and renders it as DocBook XML, CommonMark, HTML, man pages or JSON.
+//!
+//! Documentation is extracted with `parse_source`, which yields one
+//! `DocItem` per documented attribute. Items are grouped into a
+//! `Category` and rendered with `write_document` in the chosen
+//! `Format`.
+
+#[macro_use] extern crate failure_derive;
+#[macro_use] extern crate serde_derive;
+extern crate failure;
+extern crate pulldown_cmark;
+extern crate rnix;
+extern crate serde;
+#[macro_use] extern crate serde_json;
+extern crate toml;
+extern crate xml;
+
+pub mod commonmark;
+pub mod config;
+pub mod coverage;
+pub mod docbook;
+pub mod doctest;
+pub mod error;
+pub mod fulltext;
+pub mod html;
+pub mod json;
+pub mod lint;
+pub mod lsp;
+pub mod man;
+pub mod search;
+pub mod types;
+
+use self::commonmark::*;
+use self::docbook::*;
+use self::error::*;
+use self::html::*;
+use self::json::*;
+use self::types::{parse_signature, Signature, TypeError};
+use rnix::parser::{Arena, ASTNode, ASTKind, Data};
+use rnix::tokenizer::Meta;
+use rnix::tokenizer::Token;
+use rnix::tokenizer::Trivia;
+use rnix::value::Value;
+use failure::Error;
+use std::collections::HashSet;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+/// Output formats supported by nixdoc.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    DocBook,
+    CommonMark,
+    Json,
+    Html,
+    Man,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "docbook" | "xml" => Ok(Format::DocBook),
+            "markdown" | "commonmark" | "md" => Ok(Format::CommonMark),
+            "json" => Ok(Format::Json),
+            "html" => Ok(Format::Html),
+            "man" | "roff" => Ok(Format::Man),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+impl Format {
+    /// File extension used for documents in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::DocBook => "xml",
+            Format::CommonMark => "md",
+            Format::Json => "json",
+            Format::Html => "html",
+            Format::Man => man::MAN_SECTION,
+        }
+    }
+
+    /// Name of the file containing the document of a single category,
+    /// e.g. `strings.xml` or `nixpkgs-lib-strings.3` for man pages.
+    pub fn file_name(self, category: &str) -> String {
+        match self {
+            Format::Man => format!("{}.{}", man::page_name(category), self.extension()),
+            _ => format!("{}.{}", category, self.extension()),
+        }
+    }
+}
+
+/// Options controlling which comments are extracted as documentation.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Only treat RFC 145 doc comments (`/** ... */`) as documentation.
+    pub strict: bool,
+
+    /// Also extract documented `let` bindings surrounding the exported
+    /// attribute set, which are marked as internal.
+    pub include_internal: bool,
+
+    /// Also extract attributes without a documentation comment, with
+    /// an empty comment. Used to report documentation coverage.
+    pub include_undocumented: bool,
+}
+
+/// Default prefix of the fully qualified names of entries.
+pub const DEFAULT_TITLE_PREFIX: &str = "lib.";
+
+/// Default prefix of the identifiers of entries' sections.
+pub const DEFAULT_ID_PREFIX: &str = "function-library-";
+
+/// Default directory of manually written DocBook overrides.
+pub const DEFAULT_OVERRIDE_DIR: &str = "./overrides";
+
+/// Default DocBook document containing the locations of entries.
+pub const DEFAULT_LOCATIONS_INCLUDE: &str = "./locations.xml";
+
+/// Options controlling how extracted documentation is rendered.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Names of all categories of the library, which are used to link
+    /// re-exported attributes to their original definition.
+    pub categories: Vec<String>,
+
+    /// Qualified names of the documented entries of the library, see
+    /// `documented_entries`. Re-exported attributes only link to their
+    /// original definition if it is one of them.
+    pub entries: HashSet<String>,
+
+    /// URL template for links to the source location of entries, in
+    /// which `{path}` and `{line}` are replaced. Without it, DocBook
+    /// output includes the location from `locations_include`.
+    pub location_url: Option<String>,
+
+    /// Root directory of the repository, relative to which `{path}`
+    /// is substituted in `location_url`. Defaults to the current
+    /// directory.
+    pub repo_root: Option<PathBuf>,
+
+    /// URL template for links to the documentation of the type names
+    /// in signatures, in which `{name}` is replaced.
+    pub type_url: Option<String>,
+
+    /// Prefix of the fully qualified names of entries, e.g. `lib.` in
+    /// `lib.strings.concatStrings`.
+    pub title_prefix: String,
+
+    /// Prefix of the identifiers of entries' sections, which are
+    /// otherwise derived from their name.
+    pub id_prefix: String,
+
+    /// Directory from which DocBook output includes manually written
+    /// documentation (`<identifier>.xml`) overriding the entries.
+    pub override_dir: String,
+
+    /// Document from which DocBook output includes the locations of
+    /// entries, see `write_locations`.
+    pub locations_include: String,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            categories: vec![],
+            entries: HashSet::new(),
+            location_url: None,
+            repo_root: None,
+            type_url: None,
+            title_prefix: DEFAULT_TITLE_PREFIX.into(),
+            id_prefix: DEFAULT_ID_PREFIX.into(),
+            override_dir: DEFAULT_OVERRIDE_DIR.into(),
+            locations_include: DEFAULT_LOCATIONS_INCLUDE.into(),
+        }
+    }
+}
+
+/// Documentation comment attached to an attribute.
+#[derive(Debug, Clone, Default)]
+pub struct DocComment {
+    /// Primary documentation string.
+    pub doc: String,
+
+    /// Optional type annotation for the thing being documented.
+    pub doc_type: Option<String>,
+
+    /// Usage examples, each of which is a single code block.
+    pub examples: Vec<Example>,
+}
+
+/// A documented attribute extracted from a Nix file.
+#[derive(Debug, Clone, Default)]
+pub struct DocItem {
+    /// Name of the attribute.
+    pub name: String,
+
+    /// Attribute path of the set containing the attribute, relative
+    /// to the top-level set of the file (empty for top-level entries).
+    pub path: Vec<String>,
+
+    /// Parsed documentation comment of the attribute.
+    pub comment: DocComment,
+
+    /// Arguments of the function, if the attribute is one.
+    pub args: Vec<Argument>,
+
+    /// Whether the item is a private `let` binding of the file rather
+    /// than an exported attribute.
+    pub internal: bool,
+
+    /// For attributes re-exported with `inherit`, the attribute path
+    /// of the original value, e.g. `["builtins", "head"]` for
+    /// `inherit (builtins) head;`.
+    pub inherited_from: Option<Vec<String>>,
+
+    /// Location of the attribute's definition.
+    pub location: Option<Location>,
+}
+
+impl DocComment {
+    /// Parse the type annotation of the comment, see `types`.
+    pub fn signature(&self) -> Option<Result<Signature, TypeError>> {
+        self.doc_type.as_ref().map(|t| parse_signature(t))
+    }
+}
+
+impl DocItem {
+    /// Attribute path of the item within its category, e.g.
+    /// `versions.major`.
+    pub fn attr_path(&self) -> String {
+        let mut path = self.path.clone();
+        path.push(self.name.clone());
+        path.join(".")
+    }
+}
+
+/// A comment retrieved from the leading trivia of a node.
+enum RawComment {
+    /// RFC 145 doc comment (`/** ... */`), with the marker and
+    /// common indentation removed. Its content is Markdown.
+    Doc(String),
+
+    /// Any other comment, verbatim.
+    Plain(String),
+}
+
+impl RawComment {
+    /// Text of the comment, regardless of its kind.
+    fn into_text(self) -> String {
+        match self {
+            RawComment::Doc(text) | RawComment::Plain(text) => text,
+        }
+    }
+}
+
+/// Indentation common to all non-blank lines of a string.
+fn common_indentation(s: &str) -> usize {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|c| *c == ' ' || *c == '\t').count())
+        .min()
+        .unwrap_or(0)
+}
+
+/// Remove the indentation common to all non-blank lines of a string,
+/// as well as any surrounding blank lines.
+fn strip_indentation(s: &str) -> String {
+    let indent = common_indentation(s);
+
+    let lines: Vec<&str> = s.lines()
+        .map(|line| if line.trim().is_empty() { "" } else { line[indent..].trim_end() })
+        .collect();
+
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+/// Turn the content of an RFC 145 doc comment (everything between
+/// `/*` and `*/`, i.e. still including the second asterisk) into
+/// its Markdown text.
+///
+/// Text on the same line as the opening marker is not part of the
+/// indented block and is therefore handled separately.
+fn strip_doc_marker(content: &str) -> String {
+    let content = &content[1..];
+    let (first, rest) = match content.find('\n') {
+        Some(idx) => (&content[..idx], &content[idx + 1..]),
+        None => (content, ""),
+    };
+
+    let mut doc = first.trim().to_string();
+    if !doc.is_empty() {
+        doc.push('\n');
+    }
+    doc.push_str(&strip_indentation(rest));
+
+    doc.trim().to_string()
+}
+
+/// Whether the content of a multiline comment (everything between
+/// `/*` and `*/`) makes it an RFC 145 doc comment, i.e. whether the
+/// comment starts with exactly two asterisks. Empty comments such as
+/// `/**/` are not doc comments.
+fn is_doc_comment(content: &str) -> bool {
+    content.starts_with('*') && !content.starts_with("**") && !content[1..].trim().is_empty()
+}
+
+/// Retrieve documentation comments.
+///
+/// An RFC 145 doc comment (`/** ... */`) is always preferred. Unless
+/// `strict` is set, the first other multiline comment (or single-line
+/// comment, if allowed) is used if no doc comment exists.
+fn retrieve_doc_comment(allow_single_line: bool, strict: bool, meta: &Meta) -> Option<RawComment> {
+    let mut fallback = None;
+
+    for item in meta.leading.iter() {
+        if let Trivia::Comment { multiline, content, .. } = item {
+            if *multiline && is_doc_comment(content) {
+                return Some(RawComment::Doc(strip_doc_marker(content)));
+            }
+
+            if fallback.is_none() && !strict && (*multiline || allow_single_line) {
+                fallback = Some(RawComment::Plain(content.to_string()));
+            }
+        }
+    }
+
+    fallback
+}
+
+/// Retrieve the documentation of a function argument, which may be
+/// given in any kind of comment.
+fn retrieve_arg_doc(meta: &Meta) -> Option<String> {
+    retrieve_doc_comment(true, false, meta).map(RawComment::into_text)
+}
+
+/// Retrieve and parse the documentation comment in the leading
+/// trivia of a node.
+fn retrieve_parsed_comment(meta: &Meta, strict: bool) -> Option<DocComment> {
+    let comment = match retrieve_doc_comment(false, strict, meta)? {
+        RawComment::Doc(doc) => parse_markdown_comment(&doc),
+        RawComment::Plain(raw) => parse_doc_comment(&raw),
+    };
+
+    Some(comment)
+}
+
+/// The name of an attribute and the trivia preceding it, for both
+/// identifiers (`foo = ...;`) and strings (`"foo-bar" = ...;`).
+/// Strings with interpolations are not supported.
+fn attr_name(node: &ASTNode) -> Option<(&Meta, String)> {
+    match &node.data {
+        Data::Ident(meta, name) => Some((meta, name.to_string())),
+        Data::Value(meta, Value::Str { content, .. }) => Some((meta, content.to_string())),
+        _ => None,
+    }
+}
+
+/// Transforms an AST node into a `DocItem` if it has a leading
+/// documentation comment, or into one with an empty comment if
+/// undocumented items are requested.
+fn retrieve_doc_item(node: &ASTNode, opts: &ParseOptions) -> Option<DocItem> {
+    // We are only interested in attribute names.
+    let (meta, name) = attr_name(node)?;
+    let comment = match retrieve_parsed_comment(meta, opts.strict) {
+        Some(comment) => comment,
+        None if opts.include_undocumented => DocComment::default(),
+        None => return None,
+    };
+
+    Some(DocItem {
+        name,
+        path: vec![],
+        comment,
+        args: vec![],
+        internal: false,
+        inherited_from: None,
+        location: None,
+    })
+}
+
+/// The fence (e.g. ```` ``` ```` or `~~~`) opening a fenced code block
+/// in a line of Markdown, if it does.
+fn code_fence(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == marker).count();
+    if len >= 3 { Some(&line[..len]) } else { None }
+}
+
+/// Parse the Markdown of an RFC 145 doc comment. The type signature
+/// and examples are taken from the code blocks of the `# Type` and
+/// `# Examples` (or `# Example`) sections, which are removed from the
+/// description. Level two headings in the examples section are used as
+/// the titles of the following examples, unless they are the generic
+/// `... usage example` of nixpkgs.
+pub fn parse_markdown_comment(raw: &str) -> DocComment {
+    #[derive(PartialEq)]
+    enum Section { Doc, Type, Examples }
+
+    let mut doc = String::new();
+    let mut types: Vec<String> = vec![];
+    let mut examples: Vec<Example> = vec![];
+    let mut section = Section::Doc;
+    let mut title: Option<String> = None;
+
+    // The fence of the code block the current line is in, and the
+    // code of that block so far.
+    let mut fence: Option<&str> = None;
+    let mut code = String::new();
+
+    for line in raw.lines() {
+        let trimmed = line.trim();
+
+        if let Some(open) = fence {
+            let closes = trimmed.starts_with(open)
+                && trimmed.trim_start_matches(open.chars().next().unwrap_or('`')).is_empty();
+
+            if closes {
+                fence = None;
+                match section {
+                    Section::Doc => (),
+                    Section::Type => types.push(code.split_whitespace().collect::<Vec<_>>().join(" ")),
+                    Section::Examples => examples.push(Example {
+                        title: title.take(),
+                        code: strip_indentation(&code),
+                    }),
+                }
+                code.clear();
+            } else if section != Section::Doc {
+                code.push_str(line);
+                code.push('\n');
+            }
+        } else if let Some(heading) = trimmed.strip_prefix("# ") {
+            section = match heading.trim() {
+                "Type" | "Types" => Section::Type,
+                "Example" | "Examples" => Section::Examples,
+                _ => Section::Doc,
+            };
+        } else if let Some(open) = code_fence(line) {
+            fence = Some(open);
+        } else if section == Section::Examples && trimmed.starts_with("## ") {
+            let heading = trimmed[3..].trim();
+            title = if heading.ends_with("usage example") { None } else { Some(heading.to_string()) };
+        }
+
+        if section == Section::Doc {
+            doc.push_str(line);
+            doc.push('\n');
+        }
+    }
+
+    examples.retain(|example| !example.code.trim().is_empty());
+
+    DocComment {
+        doc: doc.trim().to_string(),
+        doc_type: types.into_iter().find(|t| !t.is_empty()),
+        examples,
+    }
+}
+
+/// *Really* dumb, mutable, hacky doc comment "parser".
+pub fn parse_doc_comment(raw: &str) -> DocComment {
+    enum ParseState { Doc, Type, Example }
+
+    let mut doc = String::new();
+    let mut first_line = None;
+    let mut doc_type = String::new();
+    let mut examples: Vec<Example> = vec![];
+    let mut state = ParseState::Doc;
+
+    for (idx, raw_line) in raw.trim().lines().enumerate() {
+        let mut line = raw_line.trim();
+
+        if line.starts_with("Type:") {
+            state = ParseState::Type;
+            line = &line[5..]; // trim 'Type:'
+        }
+
+        // Every 'Example:' line starts a new example. Text following
+        // it on the same line is the example's title if the example
+        // continues on the next lines, otherwise it is its code.
+        if let Some(title) = line.strip_prefix("Example:") {
+            state = ParseState::Example;
+            let title = title.trim();
+            examples.push(Example {
+                title: if title.is_empty() { None } else { Some(title.into()) },
+                code: String::new(),
+            });
+            continue;
+        }
+
+        match state {
+            ParseState::Type => doc_type.push_str(line.trim()),
+            ParseState::Doc if idx == 0 => first_line = Some(line),
+            // Like example lines, description lines keep their
+            // indentation, the common indentation is removed below.
+            ParseState::Doc => {
+                doc.push_str(raw_line);
+                doc.push('\n');
+            },
+            // Example lines keep their indentation, the indentation
+            // common to all lines of an example is removed below.
+            ParseState::Example => {
+                if let Some(example) = examples.last_mut() {
+                    example.code.push_str(raw_line);
+                    example.code.push('\n');
+                }
+            },
+        }
+    }
+
+    let f = |s: String| if s.is_empty() { None } else { Some(s.into()) };
+    for example in &mut examples {
+        if example.code.trim().is_empty() {
+            example.code = example.title.take().unwrap_or_default();
+        }
+    }
+    examples.retain(|example| !example.code.trim().is_empty());
+    for example in &mut examples {
+        example.code = strip_indentation(&example.code);
+    }
+
+    // Text on the first line follows the comment marker, it is
+    // indented like the following lines to keep their indentation
+    // relative to it.
+    if let Some(first) = first_line {
+        doc = format!("{}{}\n{}", " ".repeat(common_indentation(&doc)), first, doc);
+    }
+
+    DocComment {
+        doc: strip_indentation(&doc),
+        doc_type: f(doc_type),
+        examples,
+    }
+}
+
+/// Traverse a pattern argument, collecting its argument names.
+fn collect_pattern_args<'a>(arena: &Arena<'a>,
+                            entry: &ASTNode,
+                            args: &mut Vec<SingleArg>) -> Option<()> {
+    if let Data::Ident(meta, name) = &arena[entry.node.child?].data {
+        args.push(SingleArg {
+            name: name.to_string(),
+            doc: retrieve_arg_doc(meta),
+        });
+    }
+
+    // Recurse, but only if the entry's sibling is also an entry.
+    let next_entry = &arena[entry.node.sibling?];
+    if next_entry.kind == ASTKind::PatEntry {
+        collect_pattern_args(arena, next_entry, args);
+    }
+
+    Some(())
+}
+
+/// Traverse a Nix lambda and collect the identifiers of arguments
+/// until an unexpected AST node is encountered.
+///
+/// This will collect the argument names for curried functions in the
+/// `a: b: c: ...`-style, but does not currently work with pattern
+/// functions (`{ a, b, c }: ...`).
+///
+/// In the AST representation used by rnix, any lambda node has an
+/// immediate child that is the identifier of its argument. The "body"
+/// of the lambda is two steps to the right from that identifier, if
+/// it is a lambda the function is curried and we can recurse.
+fn collect_lambda_args<'a>(arena: &Arena<'a>,
+                           lambda_node: &ASTNode,
+                           args: &mut Vec<Argument>) -> Option<()> {
+    let ident_node = &arena[lambda_node.node.child?];
+
+    // "Flat" function arguments are represented as identifiers, ..
+    if let Data::Ident(meta, name) = &ident_node.data {
+        args.push(Argument::Flat(SingleArg {
+            name: name.to_string(),
+            doc: retrieve_arg_doc(meta),
+        }));
+    }
+
+    // ... pattern style arguments are represented as, well, patterns.
+    if ident_node.kind == ASTKind::Pattern {
+        let mut pattern_vec = vec![];
+
+        // The first child of a pattern is a token representing the
+        // opening curly brace, followed by a sibling chain of
+        // `PatEntry` nodes which each have the identifier as their
+        // first child.
+        let token_node = &arena[ident_node.node.child?];
+        let first_entry = &arena[token_node.node.sibling?];
+        collect_pattern_args(arena, first_entry, &mut pattern_vec);
+
+        if !pattern_vec.is_empty() {
+            args.push(Argument::Pattern(pattern_vec));
+        }
+    }
+
+    // Two to the right ...
+    let token_node = &arena[ident_node.node.sibling?];
+    let body_node = &arena[token_node.node.sibling?];
+
+    // Curried or not?
+    if body_node.kind == ASTKind::Lambda {
+        collect_lambda_args(arena, body_node, args);
+    }
+
+    Some(())
+}
+
+/// Split a `SetEntry` node into the identifier node carrying its
+/// documentation comment, the names of its attribute path and the
+/// node of its value.
+///
+/// The attribute path has more than one element for entries of the
+/// form `a.b.c = ...;`, in which case the identifier node is the
+/// first one.
+fn entry_parts<'a, 'b>(arena: &'b Arena<'a>,
+                       entry_node: &ASTNode) -> Option<(&'b ASTNode, Vec<String>, &'b ASTNode)> {
+    // The "root" of any attribute set entry is this `SetEntry` node.
+    // It has an `Attribute` child, which in turn has the identifiers
+    // (separated by dot tokens) as its children.
+    let attr_node = &arena[entry_node.node.child?];
+    let ident_node = &arena[attr_node.node.child?];
+
+    let mut names = vec![];
+    let mut next = attr_node.node.child;
+    while let Some(id) = next {
+        if let Some((_, name)) = attr_name(&arena[id]) {
+            names.push(name);
+        }
+        next = arena[id].node.sibling;
+    }
+
+    // The value is two nodes to the right of the attribute.
+    let assign_node = &arena[attr_node.node.sibling?];
+    let content_node = &arena[assign_node.node.sibling?];
+
+    Some((ident_node, names, content_node))
+}
+
+/// Traverse the arena from a SetEntry and collect, where possible:
+///
+/// 1. The identifier of the set entry itself.
+/// 2. The attached doc comment on the entry.
+/// 3. The argument names of any curried functions (pattern functions
+///    not yet supported).
+///
+/// `path` is the attribute path of the set containing the entry.
+pub fn collect_entry_information<'a>(arena: &Arena<'a>,
+                                     entry_node: &ASTNode,
+                                     path: &[String],
+                                     opts: &ParseOptions) -> Option<DocItem> {
+    let (ident_node, mut names, content_node) = entry_parts(arena, entry_node)?;
+
+    // At this point we can retrieve the `DocItem` from the identifier
+    // node - this already contains most of the information we are
+    // interested in.
+    let doc_item = retrieve_doc_item(ident_node, opts)?;
+
+    // Entries like `a.b = ...;` are documented as `b` in the set `a`.
+    let name = names.pop()?;
+    let mut item_path = path.to_vec();
+    item_path.extend(names);
+
+    // If the value is a lambda we can start collecting the function
+    // arguments - otherwise we're done.
+    let mut args: Vec<Argument> = vec![];
+    if content_node.kind == ASTKind::Lambda {
+        collect_lambda_args(arena, content_node, &mut args);
+    }
+
+    Some(DocItem { name, path: item_path, args, ..doc_item })
+}
+
+/// Collect the children of a node in the arena.
+fn children<'a, 'b>(arena: &'b Arena<'a>, node: &ASTNode) -> Vec<&'b ASTNode> {
+    let mut result = vec![];
+    let mut next = node.node.child;
+    while let Some(id) = next {
+        result.push(&arena[id]);
+        next = arena[id].node.sibling;
+    }
+    result
+}
+
+/// Find the attribute sets that a file evaluates to, looking through
+/// function headers (e.g. `{ lib }: ...`), `let ... in`, `with`,
+/// `assert` and parentheses.
+///
+/// Both operands of `//` are followed, as are functions applied to a
+/// lambda (e.g. `lib.fix (self: { ... })` or `makeExtensible`), whose
+/// body is the exported set.
+///
+/// The `SetEntry` nodes of all `let` expressions passed on the way
+/// are collected into `bindings`.
+fn find_exported_sets<'a, 'b>(arena: &'b Arena<'a>,
+                              node: &'b ASTNode,
+                              bindings: &mut Vec<&'b ASTNode>,
+                              sets: &mut Vec<&'b ASTNode>) -> Option<()> {
+    let nodes: Vec<&ASTNode> = children(arena, node).into_iter()
+        .filter(|child| child.kind != ASTKind::Token)
+        .collect();
+
+    match node.kind {
+        ASTKind::Set => sets.push(node),
+
+        ASTKind::Paren => find_exported_sets(arena, *nodes.first()?, bindings, sets)?,
+
+        // In all of these the expression that is evaluated is the
+        // last child, e.g. the body of a `let ... in` expression
+        // follows its bindings.
+        ASTKind::Lambda | ASTKind::With | ASTKind::Assert => {
+            find_exported_sets(arena, *nodes.last()?, bindings, sets)?
+        },
+
+        ASTKind::LetIn => {
+            let (body, entries) = nodes.split_last()?;
+            bindings.extend(entries.iter().cloned().filter(|n| n.kind == ASTKind::SetEntry));
+            find_exported_sets(arena, body, bindings, sets)?
+        },
+
+        // The children of an operation are its operands, separated by
+        // the operator token.
+        ASTKind::Operation => {
+            let is_merge = children(arena, node).iter()
+                .any(|child| matches!(child.data, Data::Token(_, Token::Merge)));
+            if !is_merge {
+                return None;
+            }
+
+            for operand in nodes {
+                find_exported_sets(arena, operand, bindings, sets);
+            }
+        },
+
+        // Only applications to a function are followed, other
+        // arguments are not part of the result.
+        ASTKind::Apply => {
+            let mut argument = *nodes.last()?;
+            while argument.kind == ASTKind::Paren {
+                argument = children(arena, argument).into_iter()
+                    .find(|child| child.kind != ASTKind::Token)?;
+            }
+
+            if argument.kind != ASTKind::Lambda {
+                return None;
+            }
+            find_exported_sets(arena, argument, bindings, sets)?
+        },
+
+        _ => return None,
+    }
+
+    Some(())
+}
+
+/// A source file from which items are being extracted.
+struct Source<'s> {
+    file: &'s Path,
+    text: &'s str,
+}
+
+impl<'s> Source<'s> {
+    /// Location of the given node in the file.
+    fn location(&self, node: &ASTNode) -> Location {
+        let (line, column) = line_column(self.text, node.span.start as usize);
+
+        Location {
+            file: self.file.display().to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+/// Determine the attribute path that an expression refers to, e.g.
+/// `["self", "strings"]` for `self.strings`.
+fn expression_path<'a>(arena: &Arena<'a>, node: &ASTNode) -> Option<Vec<String>> {
+    let nodes: Vec<&ASTNode> = children(arena, node).into_iter()
+        .filter(|child| child.kind != ASTKind::Token)
+        .collect();
+
+    match (&node.data, &node.kind) {
+        (Data::Ident(_, name), _) => Some(vec![name.to_string()]),
+        (_, ASTKind::Paren) => expression_path(arena, nodes.first()?),
+
+        // The children of an index expression are the indexed
+        // expression and the attribute, separated by a dot token.
+        (_, ASTKind::IndexSet) => {
+            let mut path = expression_path(arena, nodes.first()?)?;
+            path.extend(expression_path(arena, nodes.last()?)?);
+            Some(path)
+        },
+
+        _ => None,
+    }
+}
+
+/// Collect the names re-exported by an `inherit` statement. Each name
+/// is documented by its leading comment, or by the comment preceding
+/// the statement if only a single name is inherited. Names without a
+/// comment are still collected so that they can be documented as an
+/// alias of the original definition.
+fn collect_inherit<'a>(arena: &Arena<'a>,
+                       source: &Source,
+                       inherit_node: &ASTNode,
+                       path: &[String],
+                       internal: bool,
+                       strict: bool,
+                       items: &mut Vec<DocItem>) {
+    let nodes = children(arena, inherit_node);
+
+    // `inherit (source) a b;` has the source expression wrapped in an
+    // `InheritFrom` node between the keyword and the names.
+    let from_node = nodes.iter().find(|n| n.kind == ASTKind::InheritFrom);
+    let from_path = from_node.and_then(|from| {
+        children(arena, from).into_iter()
+            .find(|n| n.kind != ASTKind::Token)
+            .and_then(|expr| expression_path(arena, expr))
+    });
+
+    let statement_comment = match nodes.first().map(|n| &n.data) {
+        Some(Data::Token(meta, _)) => retrieve_parsed_comment(meta, strict),
+        _ => None,
+    };
+
+    let names: Vec<(&ASTNode, &Meta, String)> = nodes.iter()
+        .filter_map(|n| match &n.data {
+            Data::Ident(meta, name) => Some((*n, meta, name.to_string())),
+            _ => None,
+        })
+        .collect();
+    let single = names.len() == 1;
+
+    for (ident_node, meta, name) in names {
+        let comment = retrieve_parsed_comment(meta, strict)
+            .or_else(|| if single { statement_comment.clone() } else { None })
+            .unwrap_or_default();
+
+        // Plain `inherit a;` refers to `a` in the surrounding scope,
+        // other sources are only known if they are attribute paths.
+        let inherited_from = match from_node {
+            None => Some(vec![name.clone()]),
+            Some(_) => from_path.as_ref().map(|from_path| {
+                let mut from = from_path.clone();
+                from.push(name.clone());
+                from
+            }),
+        };
+
+        items.push(DocItem {
+            name,
+            path: path.to_vec(),
+            comment,
+            args: vec![],
+            internal,
+            inherited_from,
+            location: Some(source.location(ident_node)),
+        });
+    }
+}
+
+/// Collect the documented entries among `entries` into `items`.
+///
+/// Whenever the value of an entry is itself an attribute set, the
+/// entry's name is appended to `path` while collecting the entries of
+/// that set so that nested members are documented with their full
+/// attribute path.
+fn collect_entries<'a>(arena: &Arena<'a>,
+                       source: &Source,
+                       entries: &[&ASTNode],
+                       path: &mut Vec<String>,
+                       internal: bool,
+                       opts: &ParseOptions,
+                       items: &mut Vec<DocItem>) {
+    for entry in entries {
+        if entry.kind == ASTKind::Inherit {
+            collect_inherit(arena, source, entry, path, internal, opts.strict, items);
+            continue;
+        }
+
+        if entry.kind != ASTKind::SetEntry {
+            continue;
+        }
+
+        let (ident_node, names, content_node) = match entry_parts(arena, entry) {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        if let Some(item) = collect_entry_information(arena, entry, path, opts) {
+            let location = Some(source.location(ident_node));
+            items.push(DocItem { internal, location, ..item });
+        }
+
+        if content_node.kind == ASTKind::Set {
+            let depth = path.len();
+            path.extend(names);
+            collect_entries(arena, source, &children(arena, content_node), path, internal, opts, items);
+            path.truncate(depth);
+        }
+    }
+}
+
+/// Documentation extracted from a single file, which forms one
+/// category of library functions.
+pub struct Category {
+    /// Name of the category (e.g. 'strings', 'attrsets').
+    pub name: String,
+
+    /// Description of the category, used as its title.
+    pub description: String,
+
+    /// Documented items of the category.
+    pub items: Vec<DocItem>,
+}
+
+/// Convert extracted documentation items into manual entries of
+/// the given category.
+///
+/// Entries of nested attribute sets become children of the entry
+/// documenting the set itself, if there is one.
+pub fn manual_entries(category: &str, items: Vec<DocItem>, opts: &RenderOptions) -> Vec<ManualEntry> {
+    let mut entries: Vec<ManualEntry> = vec![];
+
+    for d in items {
+        let entry = ManualEntry {
+            category: category.to_string(),
+            name: d.name,
+            path: d.path,
+            description: d.comment.doc,
+            fn_type: d.comment.doc_type,
+            examples: d.comment.examples,
+            args: d.args,
+            internal: d.internal,
+            alias: d.inherited_from.map(|from| resolve_alias(&from, category, opts)),
+            location_url: match (&d.location, &opts.location_url) {
+                (Some(location), Some(template)) => {
+                    Some(location_url(template, location, opts.repo_root.as_deref()))
+                },
+                _ => None,
+            },
+            title_prefix: opts.title_prefix.clone(),
+            id_prefix: opts.id_prefix.clone(),
+            location: d.location,
+            children: vec![],
+        };
+
+        match find_parent(&mut entries, &entry.path) {
+            Some(parent) => parent.children.push(entry),
+            None => entries.push(entry),
+        }
+    }
+
+    entries
+}
+
+/// Path of a file relative to the root of the repository (or the
+/// current directory), with `/` as separator. Files outside of it are
+/// returned as they are.
+fn repo_path(file: &str, root: Option<&Path>) -> String {
+    let root = match root.map(PathBuf::from).or_else(|| env::current_dir().ok()) {
+        Some(root) => root.canonicalize().unwrap_or(root),
+        None => return file.to_string(),
+    };
+
+    let path = Path::new(file);
+    let path = path.canonicalize()
+        .or_else(|_| env::current_dir().map(|cwd| cwd.join(path)))
+        .unwrap_or_else(|_| path.to_path_buf());
+    match path.strip_prefix(&root) {
+        Ok(relative) => relative.components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => file.to_string(),
+    }
+}
+
+/// Build the URL of a source location from a URL template.
+fn location_url(template: &str, location: &Location, repo_root: Option<&Path>) -> String {
+    template
+        .replace("{path}", repo_path(&location.file, repo_root).trim_start_matches("./"))
+        .replace("{line}", &location.line.to_string())
+}
+
+/// Resolve the original definition of a re-exported attribute to the
+/// section documenting it, if it is one of the documented entries of
+/// the library.
+fn resolve_alias(from: &[String], category: &str, opts: &RenderOptions) -> Alias {
+    let (name, category, qualified) = match from {
+        // Plain `inherit a;` refers to a binding of the file itself,
+        // which is documented as an internal entry.
+        [binding] => (binding.clone(), category, format!("{}{}.internal.{}", opts.title_prefix, category, binding)),
+
+        _ => {
+            // References through the library itself (e.g. `self.strings.x`
+            // in nixpkgs' `lib/default.nix`) point to the category directly.
+            let path = match from.first().map(String::as_str) {
+                Some("lib") | Some("self") | Some("super") if from.len() > 2 => &from[1..],
+                _ => from,
+            };
+
+            if path.len() < 2 || !opts.categories.contains(&path[0]) {
+                return Alias {
+                    name: from.join("."),
+                    category: None,
+                    ident: None,
+                };
+            }
+
+            let qualified = format!("{}{}", opts.title_prefix, path.join("."));
+            (qualified.clone(), path[0].as_str(), qualified)
+        },
+    };
+
+    if !opts.entries.contains(&qualified) {
+        return Alias { name, category: None, ident: None };
+    }
+
+    Alias {
+        name,
+        category: Some(category.to_string()),
+        ident: Some(qualified.replace("'", "-prime")),
+    }
+}
+
+/// Qualified names of the documented entries of the given categories,
+/// e.g. `lib.strings.concatMap` or `lib.strings.internal.go` for a
+/// private binding with the title prefix `lib.`.
+pub fn documented_entries(categories: &[Category], title_prefix: &str) -> HashSet<String> {
+    let mut entries = HashSet::new();
+
+    for category in categories {
+        for item in &category.items {
+            let comment = &item.comment;
+            let undocumented = comment.doc.is_empty()
+                && comment.doc_type.is_none()
+                && comment.examples.is_empty()
+                && item.inherited_from.is_none();
+            if undocumented {
+                continue;
+            }
+
+            entries.insert(if item.internal {
+                format!("{}{}.internal.{}", title_prefix, category.name, item.attr_path())
+            } else {
+                format!("{}{}.{}", title_prefix, category.name, item.attr_path())
+            });
+        }
+    }
+
+    entries
+}
+
+/// Whether an entry documents the set at the given attribute path or
+/// one of the sets containing it.
+fn is_ancestor(entry: &ManualEntry, path: &[String]) -> bool {
+    let depth = entry.path.len();
+    depth < path.len() && entry.path[..] == path[..depth] && entry.name == path[depth]
+}
+
+/// Find the entry documenting the set at the given attribute path or,
+/// if that set is undocumented, the deepest documented set containing
+/// it.
+fn find_parent<'a>(entries: &'a mut [ManualEntry], path: &[String]) -> Option<&'a mut ManualEntry> {
+    let entry = entries.iter_mut().find(|entry| is_ancestor(entry, path))?;
+
+    if entry.path.len() + 1 == path.len() || !entry.children.iter().any(|child| is_ancestor(child, path)) {
+        return Some(entry);
+    }
+
+    find_parent(&mut entry.children, path)
+}
+
+/// Items extracted from a source file by `parse_source`.
+pub struct ParsedSource {
+    /// Documented items of the file.
+    pub items: Vec<DocItem>,
+
+    /// Problems that do not prevent extracting documentation, but
+    /// may cause parts of it to be missing.
+    pub warnings: Vec<String>,
+}
+
+/// Parse Nix source code and extract the documented items of the
+/// attribute sets it evaluates to (and, if requested, of the `let`
+/// bindings leading up to them). The path is used to report the
+/// location of syntax errors and in warnings.
+pub fn parse_source(path: &Path, src: &str, opts: &ParseOptions) -> Result<ParsedSource, NixdocError> {
+    let nix = rnix::parse(src).map_err(|(span, err)| {
+        NixdocError::parse(path, src, span.map(|s| s.start as usize), err.to_string())
+    })?;
+
+    let source = Source { file: path, text: src };
+    let mut items = vec![];
+    let mut warnings = vec![];
+    let mut bindings = vec![];
+    let mut sets = vec![];
+
+    find_exported_sets(&nix.arena, &nix.arena[nix.root], &mut bindings, &mut sets);
+    if sets.is_empty() {
+        warnings.push(format!("{}: warning: the file does not evaluate to an attribute set \
+                               that nixdoc can follow, no entries were extracted",
+                              path.display()));
+    }
+
+    for set in sets {
+        let entries = children(&nix.arena, set);
+        collect_entries(&nix.arena, &source, &entries, &mut vec![], false, opts, &mut items);
+    }
+
+    if opts.include_internal {
+        collect_entries(&nix.arena, &source, &bindings, &mut vec![], true, opts, &mut items);
+    }
+
+    Ok(ParsedSource { items, warnings })
+}
+
+/// Write a document containing the given categories in the selected
+/// output format.
+pub fn write_document<W: Write>(w: W,
+                                format: Format,
+                                categories: Vec<Category>,
+                                opts: &RenderOptions) -> Result<(), Error> {
+    match format {
+        Format::DocBook => write_docbook(w, categories, opts),
+        Format::CommonMark => write_commonmark(w, categories, opts),
+        Format::Json => write_json(w, categories),
+        Format::Html => write_html(w, categories, opts),
+        Format::Man => write_man(w, categories, opts),
+    }
+}
+
+/// Start the root section of a DocBook document, which declares the
+/// namespaces used in the document.
+fn start_docbook_root<W: Write>(w: &mut EventWriter<W>, id: &str) -> Result<(), Error> {
+    w.write(
+        XmlEvent::start_element("section")
+            .attr("xmlns", "http://docbook.org/ns/docbook")
+            .attr("xmlns:xlink", "http://www.w3.org/1999/xlink")
+            .attr("xmlns:xi", "http://www.w3.org/2001/XInclude")
+            .attr("xml:id", id))?;
+
+    w.write(XmlEvent::comment(r#"Do not edit this file manually!
+
+This file was generated using nixdoc[1]. Please edit the source Nix
+file from which this XML was generated instead.
+
+If you need to manually override the documentation of a single
+function in this file, create a new override file at
+`nixpkgs/docs/functions/library/overrides/<function-identifier>.xml`.
+
+[1]: https://github.com/tazjin/nixdoc
+"#))?;
+
+    Ok(())
+}
+
+/// Write the DocBook section of a single category. Unless it is the
+/// root of the document, the category is written as a nested section.
+fn write_category_docbook<W: Write>(w: &mut EventWriter<W>,
+                                    category: Category,
+                                    root: bool,
+                                    opts: &RenderOptions) -> Result<(), Error> {
+    let id = format!("sec-functions-library-{}", category.name);
+    if root {
+        start_docbook_root(w, &id)?;
+    } else {
+        w.write(XmlEvent::start_element("section").attr("xml:id", &id))?;
+    }
+
+    w.write(XmlEvent::start_element("title"))?;
+    w.write(XmlEvent::characters(&category.description))?;
+    w.write(XmlEvent::end_element())?;
+
+    let (internal, exported): (Vec<DocItem>, Vec<DocItem>) = category.items
+        .into_iter()
+        .partition(|item| item.internal);
+
+    for entry in manual_entries(&category.name, exported, opts) {
+        entry.write_section_xml(w, opts)?;
+    }
+
+    // Private bindings are written into a separate section.
+    if !internal.is_empty() {
+        w.write(XmlEvent::start_element("section")
+                .attr("xml:id", &format!("{}-internal", id)))?;
+        w.write(XmlEvent::start_element("title"))?;
+        w.write(XmlEvent::characters("Internal functions"))?;
+        w.write(XmlEvent::end_element())?;
+
+        for entry in manual_entries(&category.name, internal, opts) {
+            entry.write_section_xml(w, opts)?;
+        }
+
+        w.write(XmlEvent::end_element())?;
+    }
+
+    w.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Write categories as a DocBook document. Multiple categories are
+/// combined into a single library section.
+fn write_docbook<W: Write>(w: W,
+                           mut categories: Vec<Category>,
+                           opts: &RenderOptions) -> Result<(), Error> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(w);
+
+    if categories.len() == 1 {
+        return write_category_docbook(&mut writer, categories.remove(0), true, opts);
+    }
+
+    start_docbook_root(&mut writer, "sec-functions-library")?;
+    writer.write(XmlEvent::start_element("title"))?;
+    writer.write(XmlEvent::characters("Library functions"))?;
+    writer.write(XmlEvent::end_element())?;
+
+    for category in categories {
+        write_category_docbook(&mut writer, category, false, opts)?;
+    }
+
+    writer.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Write the DocBook document containing the source locations of all
+/// entries of the given categories, which is included by the entries
+/// as `locations_include` if no location URL is configured for them.
+pub fn write_locations<W: Write>(w: W,
+                                 categories: &[Category],
+                                 opts: &RenderOptions) -> Result<(), Error> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(w);
+
+    start_docbook_root(&mut writer, "sec-functions-library-locations")?;
+    writer.write(XmlEvent::start_element("title"))?;
+    writer.write(XmlEvent::characters("Locations of library functions"))?;
+    writer.write(XmlEvent::end_element())?;
+
+    for category in categories {
+        let entries = manual_entries(&category.name, category.items.clone(), opts);
+        write_locations_xml(&mut writer, &entries)?;
+    }
+
+    writer.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Write categories as a CommonMark document.
+fn write_commonmark<W: Write>(mut w: W,
+                              categories: Vec<Category>,
+                              opts: &RenderOptions) -> Result<(), Error> {
+    write_notice_md(&mut w)?;
+
+    for category in categories {
+        let (internal, exported): (Vec<DocItem>, Vec<DocItem>) = category.items
+            .into_iter()
+            .partition(|item| item.internal);
+
+        write_category_md(&mut w, &category.name, &category.description)?;
+
+        for entry in manual_entries(&category.name, exported, opts) {
+            entry.write_section_md(&mut w)?;
+        }
+
+        if !internal.is_empty() {
+            write_internal_md(&mut w, &category.name)?;
+
+            for entry in manual_entries(&category.name, internal, opts) {
+                entry.write_section_md(&mut w)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write categories as an HTML page. Links to categories that are
+/// not part of the page point to their own pages.
+fn write_html<W: Write>(mut w: W,
+                        categories: Vec<Category>,
+                        opts: &RenderOptions) -> Result<(), Error> {
+    let links = Links {
+        all: &opts.categories,
+        page: categories.iter().map(|c| c.name.clone()).collect(),
+        id_prefix: &opts.id_prefix,
+        title_prefix: &opts.title_prefix,
+        type_url: opts.type_url.as_deref(),
+    };
+
+    let title = match categories.as_slice() {
+        [category] => category.description.clone(),
+        _ => "Library functions".to_string(),
+    };
+    write_page_start(&mut w, &title, &links)?;
+
+    for category in categories {
+        let (internal, exported): (Vec<DocItem>, Vec<DocItem>) = category.items
+            .into_iter()
+            .partition(|item| item.internal);
+
+        write_category_html(&mut w, &category.name, &category.description)?;
+
+        for entry in manual_entries(&category.name, exported, opts) {
+            entry.write_section_html(&mut w, &links)?;
+        }
+
+        if !internal.is_empty() {
+            write_internal_html(&mut w, &category.name)?;
+
+            for entry in manual_entries(&category.name, internal, opts) {
+                entry.write_section_html(&mut w, &links)?;
+            }
+
+            write_section_end(&mut w)?;
+        }
+
+        write_section_end(&mut w)?;
+    }
+
+    write_page_end(&mut w)
+}
+
+/// Write categories as a man page. A single category is the page
+/// `nixpkgs-lib-<category>`, multiple categories are combined into
+/// `nixpkgs-lib` with one section per category.
+fn write_man<W: Write>(mut w: W,
+                       categories: Vec<Category>,
+                       opts: &RenderOptions) -> Result<(), Error> {
+    let single = categories.len() == 1;
+    let (name, description) = if single {
+        (man::page_name(&categories[0].name), categories[0].description.clone())
+    } else {
+        ("nixpkgs-lib".to_string(), "Nixpkgs library functions".to_string())
+    };
+
+    let pages: Vec<(String, Vec<ManualEntry>, Vec<ManualEntry>)> = categories.into_iter()
+        .map(|category| {
+            let (internal, exported): (Vec<DocItem>, Vec<DocItem>) = category.items
+                .into_iter()
+                .partition(|item| item.internal);
+            (category.description,
+             manual_entries(&category.name, exported, opts),
+             manual_entries(&category.name, internal, opts))
+        })
+        .collect();
+
+    let mut synopsis = vec![];
+    let mut examples = String::new();
+    for (_, exported, internal) in &pages {
+        for entry in exported {
+            entry.collect_synopsis(&mut synopsis);
+        }
+
+        for entry in exported.iter().chain(internal) {
+            entry.collect_examples_man(&mut examples);
+        }
+    }
+    man::write_page_start(&mut w, &name, &description, &synopsis)?;
+
+    for (category_description, exported, internal) in pages {
+        let heading = if single { "Description" } else { &category_description };
+        man::write_heading_man(&mut w, heading)?;
+
+        for entry in exported {
+            entry.write_section_man(&mut w)?;
+        }
+
+        if !internal.is_empty() {
+            man::write_heading_man(&mut w, "Internal functions")?;
+
+            for entry in internal {
+                entry.write_section_man(&mut w)?;
+            }
+        }
+    }
+
+    if !examples.is_empty() {
+        man::write_heading_man(&mut w, "Examples")?;
+        w.write_all(examples.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Write categories as a JSON document.
+fn write_json<W: Write>(mut w: W, categories: Vec<Category>) -> Result<(), Error> {
+    write_library_json(&mut w, &categories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_with_title() {
+        let comment = parse_doc_comment("Joins strings.\n\nExample: joining\n  join [ \"a\" \"b\" ]\n  => \"ab\"\n");
+        assert_eq!(comment.doc, "Joins strings.");
+        assert_eq!(comment.examples.len(), 1);
+        assert_eq!(comment.examples[0].title, Some("joining".to_string()));
+        assert_eq!(comment.examples[0].code, "join [ \"a\" \"b\" ]\n=> \"ab\"");
+    }
+
+    #[test]
+    fn relative_indentation_of_descriptions() {
+        let comment = parse_doc_comment("Joins strings:\n\n     - with a separator\n       given first\n\n       ```\n       code\n       ```\n   ");
+        assert_eq!(comment.doc, "Joins strings:\n\n- with a separator\n  given first\n\n  ```\n  code\n  ```");
+    }
+
+    #[test]
+    fn example_on_the_marker_line() {
+        let comment = parse_doc_comment("Increments.\n\nExample: foo 1 => 2\n");
+        assert_eq!(comment.examples.len(), 1);
+        assert_eq!(comment.examples[0].title, None);
+        assert_eq!(comment.examples[0].code, "foo 1 => 2");
+    }
+
+    #[test]
+    fn example_without_code() {
+        let comment = parse_doc_comment("Increments.\n\nExample:\n\nType: foo :: int -> int\n");
+        assert!(comment.examples.is_empty());
+        assert_eq!(comment.doc_type, Some("foo :: int -> int".to_string()));
+    }
+
+    #[test]
+    fn doc_comment_markers() {
+        assert!(is_doc_comment("* Docs "));
+        assert!(is_doc_comment("*\n  Docs\n"));
+        assert!(!is_doc_comment("*"));
+        assert!(!is_doc_comment("* "));
+        assert!(!is_doc_comment(""));
+        assert!(!is_doc_comment("** Not docs "));
+        assert!(!is_doc_comment(" Plain "));
+    }
+
+    #[test]
+    fn markdown_sections() {
+        let comment = parse_markdown_comment("Concatenate strings.
+
+# Inputs
+
+`list`
+: The strings
+
+# Type
+
+```
+concatStrings :: [string]
+  -> string
+```
+
+# Examples
+:::{.example}
+## `lib.strings.concatStrings` usage example
+
+```nix
+concatStrings [\"foo\" \"bar\"]
+=> \"foobar\"
+```
+
+## Empty lists
+
+```nix
+# nothing to concatenate
+concatStrings [ ]
+=> \"\"
+```
+:::
+
+# Notes
+
+```nix
+# Not a heading
+```");
+
+        assert_eq!(comment.doc, "Concatenate strings.\n\n# Inputs\n\n`list`\n: The strings\n\n# Notes\n\n```nix\n# Not a heading\n```");
+        assert_eq!(comment.doc_type, Some("concatStrings :: [string] -> string".to_string()));
+        assert_eq!(comment.examples.len(), 2);
+        assert_eq!(comment.examples[0].title, None);
+        assert_eq!(comment.examples[0].code, "concatStrings [\"foo\" \"bar\"]\n=> \"foobar\"");
+        assert_eq!(comment.examples[1].title, Some("Empty lists".to_string()));
+        assert_eq!(comment.examples[1].code, "# nothing to concatenate\nconcatStrings [ ]\n=> \"\"");
+    }
+
+    #[test]
+    fn markdown_without_sections() {
+        let comment = parse_markdown_comment("Just text.\n\n```nix\nfoo\n```");
+        assert_eq!(comment.doc, "Just text.\n\n```nix\nfoo\n```");
+        assert_eq!(comment.doc_type, None);
+        assert!(comment.examples.is_empty());
+    }
+
+    fn item(path: &str) -> DocItem {
+        let mut path = path.split('.').map(String::from).collect::<Vec<_>>();
+        DocItem {
+            name: path.pop().unwrap(),
+            path,
+            comment: DocComment::default(),
+            args: vec![],
+            internal: false,
+            inherited_from: None,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn nested_entries() {
+        let items = vec![item("versions"), item("versions.major"), item("a"), item("a.b.c"), item("x.y")];
+        let entries = manual_entries("trivial", items, &RenderOptions::default());
+
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["versions", "a", "y"]);
+        assert_eq!(entries[0].children[0].name, "major");
+
+        // `a.b` is undocumented, `a.b.c` is documented as part of `a`.
+        assert_eq!(entries[1].children.len(), 1);
+        assert_eq!(entries[1].children[0].name, "c");
+        assert_eq!(entries[1].children[0].path, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn location_urls() {
+        let location = Location { file: "/src/nixpkgs/lib/strings.nix".into(), line: 42, column: 3 };
+        let template = "https://github.com/NixOS/nixpkgs/blob/master/{path}#L{line}";
+
+        assert_eq!(location_url(template, &location, Some(Path::new("/src/nixpkgs"))),
+                   "https://github.com/NixOS/nixpkgs/blob/master/lib/strings.nix#L42");
+
+        let relative = Location { file: "./lib/strings.nix".into(), ..location };
+        assert_eq!(location_url(template, &relative, None),
+                   "https://github.com/NixOS/nixpkgs/blob/master/lib/strings.nix#L42");
+    }
+
+    fn alias_opts(entries: &[&str]) -> RenderOptions {
+        RenderOptions {
+            categories: vec!["strings".into(), "lists".into()],
+            entries: entries.iter().map(|e| e.to_string()).collect(),
+            ..RenderOptions::default()
+        }
+    }
+
+    fn path(p: &str) -> Vec<String> {
+        p.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn alias_to_documented_entry() {
+        let opts = alias_opts(&["lib.strings.concatMap"]);
+        let alias = resolve_alias(&path("self.strings.concatMap"), "lists", &opts);
+        assert_eq!(alias.name, "lib.strings.concatMap");
+        assert_eq!(alias.ident, Some("lib.strings.concatMap".to_string()));
+    }
+
+    #[test]
+    fn alias_to_missing_entry() {
+        let opts = alias_opts(&["lib.strings.concatMap"]);
+        assert_eq!(resolve_alias(&path("strings.missing"), "lists", &opts).ident, None);
+        assert_eq!(resolve_alias(&path("builtins.head"), "lists", &opts).ident, None);
+    }
+
+    #[test]
+    fn alias_to_let_binding() {
+        let opts = alias_opts(&["lib.lists.internal.go"]);
+        let alias = resolve_alias(&path("go"), "lists", &opts);
+        assert_eq!(alias.name, "go");
+        assert_eq!(alias.ident, Some("lib.lists.internal.go".to_string()));
+        assert_eq!(resolve_alias(&path("go"), "strings", &opts).ident, None);
+    }
+
+    #[test]
+    fn identifiers_with_title_prefix() {
+        let item = DocItem {
+            name: "concatMap".into(),
+            comment: DocComment { doc: "Map and concatenate.".into(), ..Default::default() },
+            ..Default::default()
+        };
+        let categories = vec![Category { name: "strings".into(), description: String::new(), items: vec![item.clone()] }];
+
+        let opts = RenderOptions {
+            categories: vec!["strings".into(), "lists".into()],
+            entries: documented_entries(&categories, "pkgs.lib."),
+            title_prefix: "pkgs.lib.".into(),
+            ..RenderOptions::default()
+        };
+        let alias = resolve_alias(&path("lib.strings.concatMap"), "lists", &opts);
+        assert_eq!(alias.name, "pkgs.lib.strings.concatMap");
+        assert_eq!(alias.ident, Some("pkgs.lib.strings.concatMap".to_string()));
+
+        let entries = manual_entries("strings", vec![item], &opts);
+        assert_eq!(entries[0].anchor(), "function-library-pkgs.lib.strings.concatMap");
+    }
+
+    fn exported_names(src: &str, include_internal: bool) -> Vec<String> {
+        let opts = ParseOptions { strict: false, include_internal, include_undocumented: false };
+        parse_source(Path::new("test.nix"), src, &opts).unwrap()
+            .items.iter().map(DocItem::attr_path).collect()
+    }
+
+    #[test]
+    fn exported_sets_of_merges() {
+        let src = "{ /* A */ a = 1; } // ({ /* B */ b = 2; } // { /* C */ c = 3; })";
+        assert_eq!(exported_names(src, false), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn exported_sets_of_let_in() {
+        let src = "{ lib }: let /* Helper */ helper = x: x; in { /* Exported */ exported = helper; }";
+        assert_eq!(exported_names(src, false), vec!["exported"]);
+        assert_eq!(exported_names(src, true), vec!["exported", "helper"]);
+    }
+
+    #[test]
+    fn exported_sets_of_fix_style_applications() {
+        assert_eq!(exported_names("lib.fix (self: { /* A */ a = 1; })", false), vec!["a"]);
+        assert_eq!(exported_names("makeExtensible (self: let x = 1; in { /* B */ b = x; })", false), vec!["b"]);
+
+        // Sets passed as plain arguments are not part of the result.
+        let opts = ParseOptions { strict: false, include_internal: false, include_undocumented: false };
+        let parsed = parse_source(Path::new("test.nix"), "f { /* A */ a = 1; }", &opts).unwrap();
+        assert!(parsed.items.is_empty());
+        assert_eq!(parsed.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements lints that compare the documentation of a
+//! function with its actual definition, to catch documentation that
+//! went stale when the function was changed.
+
+use std::fmt;
+
+use docbook::{Argument, Location};
+use {code_fence, DocItem};
+
+/// Headings of the sections of a Markdown description that list the
+/// arguments of a function, in lower case.
+const ARGUMENT_SECTIONS: &[&str] = &["inputs", "arguments", "parameters"];
+
+/// A mismatch between the documentation of an item and its definition.
+#[derive(Debug, Clone)]
+pub struct Lint {
+    /// Location of the item's definition.
+    pub location: Option<Location>,
+
+    /// Attribute path of the item within its category.
+    pub name: String,
+
+    /// Description of the mismatch.
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location.display())?;
+        }
+        write!(f, "warning: {}: {}", self.name, self.message)
+    }
+}
+
+/// Names of the arguments described in a Markdown description, as
+/// Markdown definition list terms (`` `name` `` followed by a line
+/// starting with `:`) anywhere in the description, or as list items
+/// starting with `` `name` `` in an "Inputs" (or "Arguments") section.
+/// Code blocks are skipped.
+pub fn documented_arg_names(doc: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut in_section = false;
+    let mut fence: Option<&str> = None;
+    let lines: Vec<&str> = doc.lines().map(str::trim).collect();
+
+    for (idx, line) in lines.iter().enumerate() {
+        match (fence, code_fence(line)) {
+            (None, Some(marker)) => {
+                fence = Some(marker);
+                continue;
+            },
+            (Some(open), Some(marker)) if marker.starts_with(open) => {
+                fence = None;
+                continue;
+            },
+            (Some(_), _) => continue,
+            (None, None) => (),
+        }
+
+        if line.starts_with('#') {
+            let heading = line.trim_start_matches('#').trim().to_lowercase();
+            in_section = ARGUMENT_SECTIONS.contains(&heading.as_str());
+            continue;
+        }
+
+        let item = line.strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .filter(|_| in_section);
+        let term = match item {
+            Some(item) => item.trim_start(),
+            None if matches!(lines.get(idx + 1), Some(next) if next.starts_with(':')) => line,
+            None => continue,
+        };
+
+        if let Some(term) = term.strip_prefix('`') {
+            if let Some(end) = term.find('`') {
+                names.push(term[..end].to_string());
+            }
+        }
+    }
+
+    names
+}
+
+/// Check a documented item for mismatches between its documentation
+/// and its definition.
+pub fn lint_item(item: &DocItem) -> Vec<Lint> {
+    let mut messages = vec![];
+    messages.extend(type_message(item));
+
+    let documented = documented_arg_names(&item.comment.doc);
+    let mut arg_names = vec![];
+    for arg in &item.args {
+        match arg {
+            Argument::Flat(single) => {
+                arg_names.push(single.name.as_str());
+                if single.doc.is_none() && !documented.contains(&single.name) {
+                    messages.push(format!("argument `{}` is undocumented", single.name));
+                }
+            },
+            Argument::Pattern(pattern) => for single in pattern {
+                arg_names.push(single.name.as_str());
+                if single.doc.is_none() && !documented.contains(&single.name) {
+                    messages.push(format!("attribute `{}` of the pattern argument is undocumented",
+                                          single.name));
+                }
+            },
+        }
+    }
+
+    // Only functions are checked against their type, other values may
+    // well evaluate to functions.
+    if let (Some(Ok(signature)), false) = (item.comment.signature(), item.args.is_empty()) {
+        let arity = signature.sig_type.arity();
+        if arity != item.args.len() {
+            messages.push(format!("type `{}` describes {} argument(s), but the function takes {}",
+                                  signature, arity, item.args.len()));
+        }
+    }
+
+    for name in documented {
+        if !item.args.is_empty() && !arg_names.contains(&name.as_str()) {
+            messages.push(format!("documentation refers to unknown argument `{}`", name));
+        }
+    }
+
+    let name = item.attr_path();
+    messages.into_iter()
+        .map(|message| Lint {
+            location: item.location.clone(),
+            name: name.clone(),
+            message,
+        })
+        .collect()
+}
+
+/// Message about the type signature of an item if it cannot be parsed,
+/// shared by `lint_item` and `lint_type`.
+fn type_message(item: &DocItem) -> Option<String> {
+    match item.comment.signature()? {
+        Ok(_) => None,
+        Err(err) => Some(format!("malformed type `{}`: {}",
+                                 item.comment.doc_type.as_ref()?, err)),
+    }
+}
+
+/// Check that the type signature of an item, if it has one, can be
+/// parsed.
+pub fn lint_type(item: &DocItem) -> Option<Lint> {
+    type_message(item).map(|message| Lint {
+        location: item.location.clone(),
+        name: item.attr_path(),
+        message,
+    })
+}
+
+/// Check all given items, see `lint_item`.
+pub fn lint_items(items: &[DocItem]) -> Vec<Lint> {
+    items.iter().flat_map(lint_item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_sections() {
+        let doc = "Join strings.\n\n# Inputs\n\n- `sep` the separator\n\n`list`\n: strings to join\n\n\
+                   # Examples\n\n```nix\n# Arguments\n- `notAnArg`\n```\n\n- `notAnArgEither`";
+        assert_eq!(documented_arg_names(doc), vec!["sep", "list"]);
+    }
+
+    #[test]
+    fn definition_lists_outside_of_sections() {
+        let doc = "Map over a list.\n\n`f`\n: function to apply\n\n## parameters\n\n* `list` the list";
+        assert_eq!(documented_arg_names(doc), vec!["f", "list"]);
+    }
+}
@@ -0,0 +1,541 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements a language server (speaking the Language
+//! Server Protocol over stdio) that makes the extracted documentation
+//! available in editors.
+//!
+//! The server answers hover, completion, signature help and
+//! go-to-definition requests for references to library functions,
+//! e.g. `lib.strings.concatMapStrings`, `strings.concatMapStrings` or
+//! `concatMapStrings` (for re-exports and `with lib;`). It does not
+//! evaluate anything, references are resolved by their name only.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use failure::Error;
+use serde_json::{self, Value};
+
+use docbook::Argument;
+use {Category, DocItem};
+
+/// JSON-RPC error code for messages that are not valid JSON.
+const PARSE_ERROR: i64 = -32700;
+
+/// JSON-RPC error code for unknown methods.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// LSP completion item kinds.
+const KIND_FUNCTION: u32 = 3;
+const KIND_VARIABLE: u32 = 6;
+const KIND_MODULE: u32 = 9;
+
+/// A documented library attribute.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Fully qualified name, e.g. `lib.strings.concatMapStrings`.
+    pub name: String,
+
+    /// Documentation extracted for the attribute.
+    pub item: DocItem,
+}
+
+/// Index of the documented attributes of a library.
+#[derive(Debug, Clone, Default)]
+pub struct DocIndex {
+    pub symbols: Vec<Symbol>,
+    /// Prefix of the fully qualified names, e.g. `lib.`.
+    pub title_prefix: String,
+}
+
+impl DocIndex {
+    /// Index the exported attributes of the given categories, which
+    /// are named with the given prefix (e.g. `lib.`).
+    pub fn new(categories: Vec<Category>, title_prefix: &str) -> DocIndex {
+        let mut symbols = vec![];
+
+        for category in categories {
+            for item in category.items {
+                if item.internal {
+                    continue;
+                }
+
+                symbols.push(Symbol {
+                    name: format!("{}{}.{}", title_prefix, category.name, item.attr_path()),
+                    item,
+                });
+            }
+        }
+
+        DocIndex { symbols, title_prefix: title_prefix.into() }
+    }
+
+    /// Resolve a reference to a library attribute. Attributes that are
+    /// not found in their category are looked up by their attribute
+    /// path alone, as most functions are re-exported at the top level
+    /// of `lib`. References may be qualified with the title prefix or
+    /// with `lib.`, which is how the library is usually referred to in
+    /// Nix code.
+    pub fn resolve(&self, reference: &str) -> Option<&Symbol> {
+        let path = [self.title_prefix.as_str(), "lib."].iter()
+            .filter(|prefix| !prefix.is_empty() && reference.starts_with(*prefix))
+            .map(|prefix| &reference[prefix.len()..])
+            .next()
+            .unwrap_or(reference);
+        let qualified = format!("{}{}", self.title_prefix, path);
+
+        self.symbols.iter().find(|s| s.name == qualified)
+            .or_else(|| self.symbols.iter().find(|s| s.item.attr_path() == path))
+    }
+}
+
+/// Read a single message, framed by a `Content-Length` header.
+/// Returns `None` at the end of the input. Messages that cannot be
+/// parsed are returned as an error message, so that they can be
+/// answered instead of ending the server.
+fn read_message<R: BufRead>(r: &mut R) -> Result<Option<Result<Value, String>>, Error> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let lower = line.to_lowercase();
+        if lower.starts_with("content-length:") {
+            length = Some(line["content-length:".len()..].trim().parse::<usize>());
+        }
+    }
+
+    let length = match length {
+        Some(Ok(length)) => length,
+        Some(Err(e)) => return Ok(Some(Err(format!("invalid Content-Length header: {}", e)))),
+        None => return Ok(Some(Err("message without Content-Length header".into()))),
+    };
+    let mut content = vec![0; length];
+    r.read_exact(&mut content)?;
+
+    Ok(Some(serde_json::from_slice(&content).map_err(|e| e.to_string())))
+}
+
+/// Write a single message with its `Content-Length` header.
+fn write_message<W: Write>(w: &mut W, message: &Value) -> Result<(), Error> {
+    let content = serde_json::to_string(message)?;
+    write!(w, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn is_reference_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\'' || c == '-' || c == '.'
+}
+
+/// The reference (e.g. `lib.strings.concatMapStrings`) at the given
+/// column of a line, if there is one.
+fn reference_at(line: &str, column: usize) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let column = column.min(chars.len());
+
+    let start = chars[..column].iter().rposition(|c| !is_reference_char(*c)).map_or(0, |i| i + 1);
+    let end = chars[column..].iter().position(|c| !is_reference_char(*c)).map_or(chars.len(), |i| column + i);
+
+    let reference: String = chars[start..end].iter().collect();
+    let reference = reference.trim_matches('.');
+    if reference.is_empty() { None } else { Some(reference.to_string()) }
+}
+
+/// Convert a position in a line, which the protocol counts in UTF-16
+/// code units, to a character column.
+fn utf16_to_column(line: &str, offset: usize) -> usize {
+    let mut units = 0;
+    for (column, c) in line.chars().enumerate() {
+        if units >= offset {
+            return column;
+        }
+        units += c.len_utf16();
+    }
+
+    line.chars().count()
+}
+
+/// Convert a character column of a line to UTF-16 code units.
+fn column_to_utf16(line: &str, column: usize) -> usize {
+    line.chars().take(column).map(char::len_utf16).sum()
+}
+
+/// The (partial) reference before the given column of a line.
+fn prefix_at(line: &str, column: usize) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let column = column.min(chars.len());
+    let start = chars[..column].iter().rposition(|c| !is_reference_char(*c)).map_or(0, |i| i + 1);
+    chars[start..column].iter().collect()
+}
+
+/// Flatten the arguments of a function into signature parameters,
+/// with pattern arguments represented by their attribute names.
+fn parameters(args: &[Argument]) -> Vec<(String, String)> {
+    args.iter()
+        .map(|arg| match arg {
+            Argument::Flat(single) => {
+                (single.name.clone(), single.doc.clone().unwrap_or_default().trim().to_string())
+            },
+            Argument::Pattern(pattern) => {
+                let names: Vec<&str> = pattern.iter().map(|a| a.name.as_str()).collect();
+                let docs: Vec<String> = pattern.iter()
+                    .map(|a| format!("- `{}`: {}", a.name, a.doc.as_ref().map_or("", |d| d.trim())))
+                    .collect();
+                (format!("{{ {} }}", names.join(", ")), docs.join("\n"))
+            },
+        })
+        .collect()
+}
+
+/// Markdown documentation of a symbol, shown on hover and completion.
+fn documentation(symbol: &Symbol) -> String {
+    let mut doc = String::new();
+    let comment = &symbol.item.comment;
+
+    if let Some(t) = &comment.doc_type {
+        doc.push_str(&format!("```\n{}\n```\n\n", t));
+    }
+
+    if !comment.doc.is_empty() {
+        doc.push_str(&comment.doc);
+        doc.push_str("\n\n");
+    }
+
+    if let Some(from) = &symbol.item.inherited_from {
+        doc.push_str(&format!("Alias of `{}`.\n\n", from.join(".")));
+    }
+
+    let params = parameters(&symbol.item.args);
+    if !params.is_empty() {
+        doc.push_str("**Arguments**\n\n");
+        for (name, param_doc) in params {
+            doc.push_str(&format!("- `{}`: {}\n", name, param_doc.replace('\n', "\n  ")));
+        }
+        doc.push('\n');
+    }
+
+    for example in &comment.examples {
+        match &example.title {
+            Some(title) => doc.push_str(&format!("**Example**: {}\n\n", title)),
+            None => doc.push_str("**Example**\n\n"),
+        }
+        doc.push_str(&format!("```nix\n{}\n```\n\n", example.code));
+    }
+
+    doc.trim_end().to_string()
+}
+
+/// URI of a file in the `file` scheme, with all characters of the
+/// path except for unreserved ones and separators percent-encoded.
+fn file_uri(file: &str) -> String {
+    let path = fs::canonicalize(Path::new(file))
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| file.to_string());
+
+    let mut uri = "file://".to_string();
+    for b in path.bytes() {
+        match b {
+            b'/' | b'-' | b'.' | b'_' | b'~' => uri.push(b as char),
+            _ if b.is_ascii_alphanumeric() => uri.push(b as char),
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    uri
+}
+
+/// Response to a request that failed.
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// State of the server: the documentation index and the contents of
+/// the documents opened in the editor.
+pub struct Server {
+    index: DocIndex,
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    pub fn new(index: DocIndex) -> Server {
+        Server { index, documents: HashMap::new() }
+    }
+
+    /// The line and (character) column of the position given in the
+    /// parameters of a request.
+    fn position<'a>(&'a self, params: &Value) -> Option<(&'a str, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let line = params["position"]["line"].as_u64()? as usize;
+        let offset = params["position"]["character"].as_u64()? as usize;
+
+        let text = self.documents.get(uri)?;
+        let line = text.lines().nth(line).unwrap_or("");
+        Some((line, utf16_to_column(line, offset)))
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "completionProvider": { "triggerCharacters": ["."] },
+                "signatureHelpProvider": { "triggerCharacters": [" "] },
+                "definitionProvider": true,
+            },
+            "serverInfo": { "name": "nixdoc" },
+        })
+    }
+
+    fn hover(&self, params: &Value) -> Option<Value> {
+        let (line, column) = self.position(params)?;
+        let symbol = self.index.resolve(&reference_at(line, column)?)?;
+
+        Some(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("**{}**\n\n{}", symbol.name, documentation(symbol)),
+            },
+        }))
+    }
+
+    fn completion(&self, params: &Value) -> Option<Value> {
+        let (line, column) = self.position(params)?;
+        let prefix = prefix_at(line, column);
+        let qualifier_len = prefix.rfind('.').map_or(0, |idx| idx + 1);
+
+        let mut labels: Vec<String> = vec![];
+        let mut items = vec![];
+
+        for symbol in &self.index.symbols {
+            let attr_path = symbol.item.attr_path();
+            let unprefixed = &symbol.name[self.index.title_prefix.len()..];
+            let names = [symbol.name.as_str(), unprefixed, attr_path.as_str()];
+
+            for name in names.iter().filter(|n| n.starts_with(prefix.as_str())) {
+                let rest = &name[qualifier_len..];
+                let label = rest.split('.').next().unwrap_or(rest);
+                if labels.iter().any(|l| l == label) {
+                    continue;
+                }
+                labels.push(label.to_string());
+
+                // Intermediate components are categories or sets.
+                if label.len() < rest.len() {
+                    items.push(json!({ "label": label, "kind": KIND_MODULE }));
+                    continue;
+                }
+
+                let kind = if symbol.item.args.is_empty() { KIND_VARIABLE } else { KIND_FUNCTION };
+                items.push(json!({
+                    "label": label,
+                    "kind": kind,
+                    "detail": symbol.item.comment.doc_type.clone().unwrap_or_else(|| symbol.name.clone()),
+                    "documentation": { "kind": "markdown", "value": documentation(symbol) },
+                }));
+            }
+        }
+
+        Some(json!(items))
+    }
+
+    fn signature_help(&self, params: &Value) -> Option<Value> {
+        let (line, column) = self.position(params)?;
+        let before: String = line.chars().take(column).collect();
+        let tokens: Vec<&str> = before.split_whitespace().collect();
+
+        // The function is the last reference to a known function
+        // before the cursor, its arguments are the tokens following it.
+        let (position, symbol) = tokens.iter().enumerate().rev()
+            .filter_map(|(idx, token)| {
+                let symbol = self.index.resolve(token.trim_start_matches('('))?;
+                if symbol.item.args.is_empty() { None } else { Some((idx, symbol)) }
+            })
+            .next()?;
+
+        let params = parameters(&symbol.item.args);
+        let following = tokens.len() - position - 1;
+        let active = if before.ends_with(char::is_whitespace) { following } else { following.saturating_sub(1) };
+
+        let label = format!("{} {}", symbol.name,
+                            params.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(" "));
+        let parameters: Vec<Value> = params.iter()
+            .map(|(name, doc)| json!({ "label": name, "documentation": doc }))
+            .collect();
+
+        Some(json!({
+            "signatures": [{
+                "label": label,
+                "documentation": { "kind": "markdown", "value": documentation(symbol) },
+                "parameters": parameters,
+            }],
+            "activeSignature": 0,
+            "activeParameter": active.min(params.len() - 1),
+        }))
+    }
+
+    fn definition(&self, params: &Value) -> Option<Value> {
+        let (line, column) = self.position(params)?;
+        let symbol = self.index.resolve(&reference_at(line, column)?)?;
+        let location = symbol.item.location.as_ref()?;
+
+        // Positions are counted in UTF-16 code units, which requires
+        // the text of the line.
+        let character = fs::read_to_string(&location.file).ok()
+            .and_then(|text| text.lines().nth(location.line - 1).map(|line| column_to_utf16(line, location.column - 1)))
+            .unwrap_or(location.column - 1);
+
+        let position = json!({ "line": location.line - 1, "character": character });
+        Some(json!({
+            "uri": file_uri(&location.file),
+            "range": { "start": position, "end": position },
+        }))
+    }
+
+    /// Handle a notification, which does not get a response.
+    fn notify(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri, text.to_string());
+            },
+
+            // Documents are synchronised in full, so the last change
+            // contains the whole text.
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|c| c.last()).and_then(|c| c["text"].as_str()) {
+                    self.documents.insert(uri, text.to_string());
+                }
+            },
+
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            },
+
+            _ => (),
+        }
+    }
+
+    /// Handle a request, returning its result or an error code and
+    /// message.
+    fn request(&self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let result = match method {
+            "initialize" => Some(self.initialize()),
+            "shutdown" => None,
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/signatureHelp" => self.signature_help(params),
+            "textDocument/definition" => self.definition(params),
+            _ => return Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+
+        Ok(result.unwrap_or(Value::Null))
+    }
+
+    /// Serve requests until the client exits or closes the input.
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> Result<(), Error> {
+        while let Some(message) = read_message(&mut input)? {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    write_message(&mut output, &error_response(&Value::Null, PARSE_ERROR, &error))?;
+                    continue;
+                },
+            };
+
+            let method = match message["method"].as_str() {
+                Some(method) => method,
+                // Responses to requests of the server, which it does
+                // not send.
+                None => continue,
+            };
+
+            if method == "exit" {
+                break;
+            }
+
+            let params = &message["params"];
+            let id = match message.get("id") {
+                Some(id) => id,
+                None => {
+                    self.notify(method, params);
+                    continue;
+                },
+            };
+
+            let response = match self.request(method, params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, error)) => error_response(id, code, &error),
+            };
+
+            write_message(&mut output, &response)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(content: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    #[test]
+    fn utf16_positions() {
+        let line = "x = \u{1F600} lib.id";
+        assert_eq!(utf16_to_column(line, 7), 6);
+        assert_eq!(column_to_utf16(line, 6), 7);
+        assert_eq!(reference_at(line, utf16_to_column(line, 9)), Some("lib.id".into()));
+        assert_eq!(utf16_to_column(line, 100), line.chars().count());
+    }
+
+    #[test]
+    fn file_uris_are_encoded() {
+        assert_eq!(file_uri("/nonexistent/my lib/a#b%.nix"),
+                   "file:///nonexistent/my%20lib/a%23b%25.nix");
+    }
+
+    #[test]
+    fn malformed_messages_are_answered() {
+        let input = frame("{ not json") + "Content-Length: x\r\n\r\n"
+            + &frame(r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#);
+        let mut output = vec![];
+        Server::new(DocIndex::default()).serve(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("-32700").count(), 2, "{}", output);
+        assert!(output.contains(r#""result":null"#), "{}", output);
+    }
+}
@@ -13,301 +13,674 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-//! This tool generates DocBook XML from a Nix file defining library
-//! functions, such as the files in `lib/` in the nixpkgs repository.
-//!
-//! TODO:
-//! * extract function argument names
-//! * extract line number & add it to generated output
-//! * figure out how to specify examples (& leading whitespace?!)
+//! Command line interface of nixdoc, see the library crate for the
+//! actual documentation extraction and rendering.
 
 #[macro_use] extern crate structopt;
-extern crate xml;
-extern crate failure;
-extern crate rnix;
-
-mod docbook;
-
-use self::docbook::*;
-use rnix::parser::{Arena, ASTNode, ASTKind, Data};
-use rnix::tokenizer::Meta;
-use rnix::tokenizer::Trivia;
-use std::fs;
+extern crate nixdoc;
+
+use nixdoc::config::{Config, FileConfig, CONFIG_FILE};
+use nixdoc::coverage::{write_coverage_report, Coverage};
+use nixdoc::doctest::{check_examples, collect_doctests, write_doctests};
+use nixdoc::error::NixdocError;
+use nixdoc::fulltext::SearchIndex;
+use nixdoc::html::{write_index_html, Links};
+use nixdoc::lint::{lint_items, lint_type};
+use nixdoc::lsp::{DocIndex, Server};
+use nixdoc::search::{write_results, TypeIndex};
+use nixdoc::types::parse_signature;
+use nixdoc::{documented_entries, parse_source, write_document, write_locations, Category, Format, ParseOptions, RenderOptions};
+use nixdoc::{DEFAULT_ID_PREFIX, DEFAULT_LOCATIONS_INCLUDE, DEFAULT_OVERRIDE_DIR, DEFAULT_TITLE_PREFIX};
+use std::fmt::Display;
+use std::fs::{self, File};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
 use structopt::StructOpt;
-use xml::writer::{EmitterConfig, XmlEvent};
+
+/// Revision of the repository that definitions link to by default.
+const DEFAULT_REVISION: &str = "master";
 
 /// Command line arguments for nixdoc
 #[derive(Debug, StructOpt)]
 #[structopt(name = "nixdoc", about = "Generate Docbook from Nix library functions")]
 struct Options {
-    /// Nix file to process.
+    /// Nix files to process. Directories are expanded to the `.nix`
+    /// files they contain. Defaults to the files listed in the
+    /// configuration file.
     #[structopt(short = "f", long = "file", parse(from_os_str))]
-    file: PathBuf,
+    files: Vec<PathBuf>,
+
+    /// Configuration file to read instead of './nixdoc.toml' (which
+    /// is read if it exists).
+    #[structopt(long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
 
     /// Name of the function category (e.g. 'strings', 'attrsets').
+    /// Defaults to the file name without its extension, can only be
+    /// set when processing a single file.
     #[structopt(short = "c", long = "category")]
-    category: String,
+    category: Option<String>,
 
-    /// Description of the function category.
+    /// Description of the function category. Can only be set when
+    /// processing a single file.
     #[structopt(short = "d", long = "description")]
-    description: String,
+    description: Option<String>,
+
+    /// Write one document per category into this directory, instead
+    /// of a single combined document to stdout.
+    #[structopt(short = "o", long = "output-dir", parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+
+    /// Only treat RFC 145 doc comments (`/** ... */`) as documentation.
+    #[structopt(long = "strict")]
+    strict: bool,
+
+    /// Also document private `let` bindings of the files, in a
+    /// separate "internal" section.
+    #[structopt(long = "include-internal")]
+    include_internal: bool,
+
+    /// Output format ('docbook', 'markdown', 'html', 'man' or 'json').
+    /// Defaults to 'docbook'.
+    #[structopt(short = "F", long = "format")]
+    format: Option<Format>,
+
+    /// Prefix of the fully qualified names of entries. Defaults to 'lib.'.
+    #[structopt(long = "title-prefix")]
+    title_prefix: Option<String>,
+
+    /// Prefix of the identifiers of entries' sections. Defaults to
+    /// 'function-library-'.
+    #[structopt(long = "id-prefix")]
+    id_prefix: Option<String>,
+
+    /// Directory from which DocBook output includes manually written
+    /// documentation overriding entries. Defaults to './overrides'.
+    #[structopt(long = "override-dir")]
+    override_dir: Option<String>,
+
+    /// Document from which DocBook output includes the locations of
+    /// entries if no location URL is configured. Defaults to
+    /// './locations.xml'.
+    #[structopt(long = "locations-include")]
+    locations_include: Option<String>,
+
+    /// URL of the repository containing the Nix files. Entries link to
+    /// their definition in it (e.g. 'https://github.com/NixOS/nixpkgs').
+    #[structopt(long = "repo-url")]
+    repo_url: Option<String>,
+
+    /// Root directory of the repository, relative to which the paths
+    /// of files are used in links to definitions. Defaults to the
+    /// current directory.
+    #[structopt(long = "repo-root", parse(from_os_str))]
+    repo_root: Option<PathBuf>,
+
+    /// Revision of the repository to link to. Defaults to 'master'.
+    #[structopt(long = "revision")]
+    revision: Option<String>,
+
+    /// Template for links to definitions. '{repo}', '{revision}',
+    /// '{path}' and '{line}' are replaced by their values. Defaults to
+    /// '{repo}/blob/{revision}/{path}#L{line}' if --repo-url is set.
+    #[structopt(long = "location-url")]
+    location_url: Option<String>,
+
+    /// Template for links to the documentation of type names in type
+    /// signatures, in which '{name}' is replaced by the name.
+    #[structopt(long = "type-url")]
+    type_url: Option<String>,
+
+    /// Write a DocBook document containing the locations of all
+    /// entries to this file. It is included by the entries (see
+    /// --locations-include) if no location URL is configured.
+    #[structopt(long = "locations", parse(from_os_str))]
+    locations: Option<PathBuf>,
+
+    /// Instead of generating documentation, report which exported
+    /// attributes and arguments are documented.
+    #[structopt(long = "coverage")]
+    coverage: bool,
+
+    /// Fail if less than this percentage of the exported attributes
+    /// is documented. Implies --coverage.
+    #[structopt(long = "min-coverage")]
+    min_coverage: Option<f64>,
+
+    /// Warn about documentation that does not match the definition it
+    /// documents, e.g. a type with the wrong number of arguments, and
+    /// fail after writing the output if there are any warnings.
+    #[structopt(long = "lint")]
+    lint: bool,
+
+    /// Write the index of all type signatures to this file as JSON,
+    /// which is searched with the 'search' subcommand.
+    #[structopt(long = "type-index", parse(from_os_str))]
+    type_index: Option<PathBuf>,
+
+    /// Write a full-text search index of all entries to this file as
+    /// JSON, for client-side search in the generated documentation.
+    #[structopt(long = "search-index", parse(from_os_str))]
+    search_index: Option<PathBuf>,
+
+    /// Run a language server on stdin and stdout that provides the
+    /// documentation of the files to editors.
+    #[structopt(long = "lsp")]
+    lsp: bool,
+
+    /// Only check the documentation (examples and type signatures)
+    /// instead of generating it, and fail if examples are not valid Nix
+    /// or type signatures are malformed. Can be combined with --coverage.
+    #[structopt(long = "check")]
+    check: bool,
+
+    /// Write the `expr => result` pairs of all examples to this file
+    /// as tests for `lib.runTests`.
+    #[structopt(long = "doctests", parse(from_os_str))]
+    doctests: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug)]
-struct DocComment {
-    /// Primary documentation string.
-    doc: String,
+/// Subcommands of nixdoc, which work on previously generated indices
+/// instead of Nix files.
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// List the functions whose type matches the given signature
+    /// (e.g. '[a] -> AttrSet'), best matches first.
+    #[structopt(name = "search")]
+    Search {
+        /// Type index written with --type-index.
+        #[structopt(long = "type-index", parse(from_os_str))]
+        type_index: PathBuf,
+
+        /// Maximum number of functions listed.
+        #[structopt(long = "max-results", default_value = "20")]
+        max_results: usize,
+
+        /// Signature to search for.
+        query: String,
+    },
+}
 
-    /// Optional type annotation for the thing being documented.
-    doc_type: Option<String>,
+/// Expand the paths given on the command line into the list of Nix
+/// files to process. Directories are expanded (non-recursively) to
+/// the `.nix` files they contain, in alphabetical order.
+fn expand_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, NixdocError> {
+    let mut files = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            let mut dir_files = vec![];
+            let entries = fs::read_dir(path).map_err(|e| NixdocError::read(path, e))?;
+            for entry in entries {
+                let file = entry.map_err(|e| NixdocError::read(path, e))?.path();
+                if file.is_file() && file.extension() == Some("nix".as_ref()) {
+                    dir_files.push(file);
+                }
+            }
 
-    /// Usage example(s) (interpreted as a single code block)
-    example: Option<String>,
-}
+            dir_files.sort();
+            files.extend(dir_files);
+        } else {
+            files.push(path.clone());
+        }
+    }
 
-#[derive(Debug)]
-struct DocItem {
-    name: String,
-    comment: DocComment,
-    args: Vec<Argument>,
+    Ok(files)
 }
 
-/// Retrieve documentation comments. For now only multiline comments
-/// starting with `@doc` are considered.
-fn retrieve_doc_comment(allow_single_line: bool, meta: &Meta) -> Option<String> {
-    for item in meta.leading.iter() {
-        if let Trivia::Comment { multiline, content, .. } = item {
-            if *multiline || allow_single_line {
-                return Some(content.to_string())
+/// Files listed in the configuration. Directories are expanded like
+/// those given on the command line, except for files that are listed
+/// on their own (which keep their settings and position).
+fn config_files(config: &Config) -> Result<Vec<FileConfig>, NixdocError> {
+    let mut files = vec![];
+
+    for file in &config.files {
+        if !file.path.is_dir() {
+            files.push(file.clone());
+            continue;
+        }
+
+        if file.category.is_some() || file.description.is_some() {
+            return Err(NixdocError::Usage(format!(
+                "directory {} in {} cannot have a category or description, \
+                 list its files separately instead", file.path.display(), CONFIG_FILE
+            )));
+        }
+
+        for path in expand_paths(slice::from_ref(&file.path))? {
+            if config.file(&path).is_none() {
+                files.push(FileConfig::new(path));
             }
         }
     }
 
-    return None;
+    Ok(files)
 }
 
-/// Transforms an AST node into a `DocItem` if it has a leading
-/// documentation comment.
-fn retrieve_doc_item(node: &ASTNode) -> Option<DocItem> {
-    // We are only interested in identifiers.
-    if let Data::Ident(meta, name) = &node.data {
-        let comment = retrieve_doc_comment(false, meta)?;
-
-        return Some(DocItem {
-            name: name.to_string(),
-            comment: parse_doc_comment(&comment),
-            args: vec![],
-        })
+/// Construct an error for a failed write to the given file.
+fn write_error(path: &Path, e: &dyn Display) -> NixdocError {
+    NixdocError::Write {
+        target: path.display().to_string(),
+        message: e.to_string(),
+    }
+}
+
+/// Check that no two categories have the same name, as they are
+/// written to files named after them which must not overwrite each
+/// other.
+fn check_distinct_categories(categories: &[Category]) -> Result<(), NixdocError> {
+    for (idx, category) in categories.iter().enumerate() {
+        if categories[..idx].iter().any(|other| other.name == category.name) {
+            return Err(NixdocError::Usage(format!(
+                "category '{}' is used for several files, set distinct categories \
+                 with --category or in {}", category.name, CONFIG_FILE
+            )));
+        }
     }
 
-    return None;
+    Ok(())
 }
 
-/// *Really* dumb, mutable, hacky doc comment "parser".
-fn parse_doc_comment(raw: &str) -> DocComment {
-    enum ParseState { Doc, Type, Example }
+/// Derive the category name from a file name, e.g. `strings` for
+/// `lib/strings.nix`.
+fn category_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
 
-    let mut doc = String::new();
-    let mut doc_type = String::new();
-    let mut example = String::new();
-    let mut state = ParseState::Doc;
+/// Read a single Nix file and extract all documented items from it.
+fn read_category(path: &Path,
+                 name: String,
+                 description: String,
+                 opts: &ParseOptions) -> Result<Category, NixdocError> {
+    let src = fs::read_to_string(path).map_err(|e| NixdocError::read(path, e))?;
+    let parsed = parse_source(path, &src, opts)?;
 
-    for line in raw.trim().lines() {
-        let mut line = line.trim();
+    for warning in &parsed.warnings {
+        eprintln!("{}", warning);
+    }
 
-        if line.starts_with("Type:") {
-            state = ParseState::Type;
-            line = &line[5..]; // trim 'Type:'
-        }
+    Ok(Category { name, description, items: parsed.items })
+}
 
-        if line.starts_with("Example:") {
-            state = ParseState::Example;
-            line = &line[8..]; // trim 'Example:'
-        }
+/// Print the coverage report of the given categories and check that
+/// the total coverage satisfies the threshold, if one is given.
+fn check_coverage(categories: &[Category], threshold: Option<f64>) -> Result<(), NixdocError> {
+    let coverage: Vec<Coverage> = categories.iter().map(Coverage::of_category).collect();
+
+    let stdout = io::stdout();
+    write_coverage_report(&mut stdout.lock(), &coverage)
+        .map_err(|e| NixdocError::Write {
+            target: "stdout".into(),
+            message: e.to_string(),
+        })?;
+
+    match threshold {
+        Some(threshold) => Coverage::total(&coverage).check_threshold(threshold),
+        None => Ok(()),
+    }
+}
 
-        match state {
-            ParseState::Type => doc_type.push_str(line.trim()),
-            ParseState::Doc => {
-                doc.push_str(line.trim());
-                doc.push('\n');
-            },
-            ParseState::Example => {
-                example.push_str(line.trim());
-                example.push('\n');
-            },
-        }
+/// Print the functions of the type index in the given file that match
+/// the query signature.
+fn search_type(index_path: &Path, query: &str, max_results: usize) -> Result<(), NixdocError> {
+    let query = parse_signature(query)
+        .map_err(|e| NixdocError::Usage(format!("invalid type query: {}", e)))?;
+
+    let file = File::open(index_path).map_err(|e| NixdocError::read(index_path, e))?;
+    let index = TypeIndex::read_json(file).map_err(|e| NixdocError::Usage(
+        format!("invalid type index {}: {}", index_path.display(), e)
+    ))?;
+
+    let results = index.search(&query.sig_type);
+    let stdout = io::stdout();
+    write_results(&mut stdout.lock(), &results[..results.len().min(max_results)])
+        .map_err(|e| NixdocError::Write {
+            target: "stdout".into(),
+            message: e.to_string(),
+        })
+}
+
+/// Settings of the rendering, from the command line flags or, if they
+/// are not given, the configuration.
+fn render_options(opts: &Options, config: &Config, categories: &[Category]) -> RenderOptions {
+    let title_prefix = opts.title_prefix.clone()
+        .or_else(|| config.title_prefix.clone())
+        .unwrap_or_else(|| DEFAULT_TITLE_PREFIX.into());
+    let revision = opts.revision.clone()
+        .or_else(|| config.revision.clone())
+        .unwrap_or_else(|| DEFAULT_REVISION.into());
+
+    let location_url = opts.location_url.clone()
+        .or_else(|| opts.repo_url.as_ref().map(|_| "{repo}/blob/{revision}/{path}#L{line}".into()))
+        .map(|template| template
+             .replace("{repo}", opts.repo_url.as_ref().map_or("", |r| r.trim_end_matches('/')))
+             .replace("{revision}", &revision));
+
+    RenderOptions {
+        categories: categories.iter().map(|c| c.name.clone()).collect(),
+        entries: documented_entries(categories, &title_prefix),
+        location_url,
+        repo_root: opts.repo_root.clone().or_else(|| config.repo_root.clone()),
+        type_url: opts.type_url.clone().or_else(|| config.type_url.clone()),
+        title_prefix,
+        id_prefix: opts.id_prefix.clone()
+            .or_else(|| config.id_prefix.clone())
+            .unwrap_or_else(|| DEFAULT_ID_PREFIX.into()),
+        override_dir: opts.override_dir.clone()
+            .or_else(|| config.override_dir.clone())
+            .unwrap_or_else(|| DEFAULT_OVERRIDE_DIR.into()),
+        locations_include: opts.locations_include.clone()
+            .or_else(|| config.locations_include.clone())
+            .unwrap_or_else(|| DEFAULT_LOCATIONS_INCLUDE.into()),
     }
+}
 
-    let f = |s: String| if s.is_empty() { None } else { Some(s.into()) };
+fn main() {
+    let opts = Options::from_args();
 
-    DocComment {
-        doc: doc.trim().into(),
-        doc_type: f(doc_type),
-        example: f(example),
+    if let Err(err) = run(opts) {
+        eprintln!("error: {}", err);
+        process::exit(err.exit_code());
     }
 }
 
-/// Traverse a pattern argument, collecting its argument names.
-fn collect_pattern_args<'a>(arena: &Arena<'a>,
-                            entry: &ASTNode,
-                            args: &mut Vec<SingleArg>) -> Option<()> {
-    if let Data::Ident(meta, name) = &arena[entry.node.child?].data {
-        args.push(SingleArg {
-            name: name.to_string(),
-            doc: retrieve_doc_comment(true, meta),
-        });
+/// Read the configuration file given on the command line or, if there
+/// is none, the one in the current directory if it exists.
+fn read_config(path: &Option<PathBuf>) -> Result<Config, NixdocError> {
+    match path {
+        Some(path) => Config::load(path),
+        None if Path::new(CONFIG_FILE).is_file() => Config::load(Path::new(CONFIG_FILE)),
+        None => Ok(Config::default()),
     }
+}
 
-    // Recurse, but only if the entry's sibling is also an entry.
-    let next_entry = &arena[entry.node.sibling?];
-    if next_entry.kind == ASTKind::PatEntry {
-        collect_pattern_args(arena, next_entry, args);
+fn run(opts: Options) -> Result<(), NixdocError> {
+    if let Some(Command::Search { type_index, max_results, query }) = &opts.command {
+        return search_type(type_index, query, *max_results);
     }
 
-    Some(())
-}
+    let config = read_config(&opts.config)?;
+    let format = opts.format.or(config.format).unwrap_or(Format::DocBook);
 
-/// Traverse a Nix lambda and collect the identifiers of arguments
-/// until an unexpected AST node is encountered.
-///
-/// This will collect the argument names for curried functions in the
-/// `a: b: c: ...`-style, but does not currently work with pattern
-/// functions (`{ a, b, c }: ...`).
-///
-/// In the AST representation used by rnix, any lambda node has an
-/// immediate child that is the identifier of its argument. The "body"
-/// of the lambda is two steps to the right from that identifier, if
-/// it is a lambda the function is curried and we can recurse.
-fn collect_lambda_args<'a>(arena: &Arena<'a>,
-                           lambda_node: &ASTNode,
-                           args: &mut Vec<Argument>) -> Option<()> {
-    let ident_node = &arena[lambda_node.node.child?];
-
-    // "Flat" function arguments are represented as identifiers, ..
-    if let Data::Ident(meta, name) = &ident_node.data {
-        args.push(Argument::Flat(SingleArg {
-            name: name.to_string(),
-            doc: retrieve_doc_comment(true, meta),
-        }));
+    // Files given on the command line replace those listed in the
+    // configuration, but keep their category and description.
+    let files: Vec<FileConfig> = if opts.files.is_empty() {
+        config_files(&config)?
+    } else {
+        expand_paths(&opts.files)?.into_iter()
+            .map(|path| config.file(&path).cloned().unwrap_or_else(|| FileConfig::new(path)))
+            .collect()
+    };
+
+    if files.is_empty() {
+        return Err(NixdocError::Usage(
+            format!("no files given, pass them with --file or list them in {}", CONFIG_FILE)
+        ));
     }
 
-    // ... pattern style arguments are represented as, well, patterns.
-    if ident_node.kind == ASTKind::Pattern {
-        let mut pattern_vec = vec![];
+    let parse_opts = ParseOptions {
+        strict: opts.strict,
+        include_internal: opts.include_internal,
+        include_undocumented: opts.coverage || opts.min_coverage.is_some(),
+    };
+
+    if files.len() != 1 && (opts.category.is_some() || opts.description.is_some()) {
+        return Err(NixdocError::Usage(
+            "--category and --description can only be used with a single file".into()
+        ));
+    }
+
+    let mut categories = vec![];
+    for file in &files {
+        let name = opts.category.clone()
+            .or_else(|| file.category.clone())
+            .unwrap_or_else(|| category_name(&file.path));
+        let description = opts.description.clone()
+            .or_else(|| file.description.clone())
+            .unwrap_or_else(|| format!("{} functions", name));
+        categories.push(read_category(&file.path, name, description, &parse_opts)?);
+    }
+
+    let render_opts = render_options(&opts, &config, &categories);
+
+    if opts.lsp {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        return Server::new(DocIndex::new(categories, &render_opts.title_prefix))
+            .serve(stdin.lock(), stdout.lock())
+            .map_err(|e| NixdocError::Write {
+                target: "language server client".into(),
+                message: e.to_string(),
+            });
+    }
+
+    // Syntax errors in examples and malformed type signatures are
+    // always reported, the other lints only on request.
+    let mut example_errors = 0;
+    let mut malformed_types = 0;
+    let mut lint_warnings = 0;
+    for category in &categories {
+        malformed_types += category.items.iter().filter_map(lint_type).count();
+
+        let lints = if opts.lint {
+            lint_items(&category.items)
+        } else {
+            category.items.iter().filter_map(lint_type).collect()
+        };
+
+        if opts.lint {
+            lint_warnings += lints.len();
+        }
 
-        // The first child of a pattern is a token representing the
-        // opening curly brace, followed by a sibling chain of
-        // `PatEntry` nodes which each have the identifier as their
-        // first child.
-        let token_node = &arena[ident_node.node.child?];
-        let first_entry = &arena[token_node.node.sibling?];
-        collect_pattern_args(arena, first_entry, &mut pattern_vec);
+        for lint in lints {
+            eprintln!("{}", lint);
+        }
 
-        if !pattern_vec.is_empty() {
-            args.push(Argument::Pattern(pattern_vec));
+        for error in check_examples(category, &render_opts.title_prefix) {
+            eprintln!("{}", error);
+            example_errors += 1;
         }
     }
 
-    // Two to the right ...
-    let token_node = &arena[ident_node.node.sibling?];
-    let body_node = &arena[token_node.node.sibling?];
+    // Documentation is still generated if lints fail, which are
+    // reported once it is written.
+    let lint_result = if lint_warnings > 0 {
+        Err(NixdocError::Lint { lints: lint_warnings })
+    } else {
+        Ok(())
+    };
+
+    // The check and coverage modes only report on the documentation
+    // instead of generating it, and may be combined.
+    if opts.check || parse_opts.include_undocumented {
+        let coverage = if parse_opts.include_undocumented {
+            check_coverage(&categories, opts.min_coverage)
+        } else {
+            Ok(())
+        };
+
+        if opts.check && example_errors + malformed_types > 0 {
+            return Err(NixdocError::Check {
+                examples: example_errors,
+                signatures: malformed_types,
+            });
+        }
 
-    // Curried or not?
-    if body_node.kind == ASTKind::Lambda {
-        collect_lambda_args(arena, body_node, args);
+        return lint_result.and(coverage);
     }
 
-    Some(())
-}
+    if let Some(path) = &opts.locations {
+        let file = File::create(path).map_err(|e| write_error(path, &e))?;
+        write_locations(file, &categories, &render_opts).map_err(|e| write_error(path, &e))?;
+    }
 
-/// Traverse the arena from a top-level SetEntry and collect, where
-/// possible:
-///
-/// 1. The identifier of the set entry itself.
-/// 2. The attached doc comment on the entry.
-/// 3. The argument names of any curried functions (pattern functions
-///    not yet supported).
-fn collect_entry_information<'a>(arena: &Arena<'a>, entry_node: &ASTNode) -> Option<DocItem> {
-    // The "root" of any attribute set entry is this `SetEntry` node.
-    // It has an `Attribute` child, which in turn has the identifier
-    // (on which the documentation comment is stored) as its child.
-    let attr_node = &arena[entry_node.node.child?];
-    let ident_node = &arena[attr_node.node.child?];
-
-    // At this point we can retrieve the `DocItem` from the identifier
-    // node - this already contains most of the information we are
-    // interested in.
-    let doc_item = retrieve_doc_item(ident_node)?;
-
-    // From our entry we can walk two nodes to the right and check
-    // whether we are dealing with a lambda. If so, we can start
-    // collecting the function arguments - otherwise we're done.
-    let assign_node = &arena[attr_node.node.sibling?];
-    let content_node = &arena[assign_node.node.sibling?];
-
-    if content_node.kind == ASTKind::Lambda {
-        let mut args: Vec<Argument> = vec![];
-        collect_lambda_args(arena, content_node, &mut args);
-        Some(DocItem { args, ..doc_item })
-    } else {
-        Some(doc_item)
+    if let Some(path) = &opts.doctests {
+        let tests = collect_doctests(&categories, &render_opts.title_prefix);
+        let mut file = File::create(path).map_err(|e| write_error(path, &e))?;
+        write_doctests(&mut file, &tests).map_err(|e| write_error(path, &e))?;
     }
-}
 
-fn main() {
-    let opts = Options::from_args();
-    let src = fs::read_to_string(&opts.file).unwrap();
-    let nix = rnix::parse(&src).unwrap();
-
-    let entries: Vec<ManualEntry> = nix.arena.into_iter()
-        .filter(|node| node.kind == ASTKind::SetEntry)
-        .filter_map(|node| collect_entry_information(&nix.arena, node))
-        .map(|d| ManualEntry {
-            category: opts.category.clone(),
-            name: d.name,
-            description: d.comment.doc
-                .split("\n\n")
-                .map(|s| s.to_string())
-                .collect(),
-            fn_type: d.comment.doc_type,
-            example: d.comment.example,
-            args: d.args,
-        })
-        .collect();
+    if let Some(path) = &opts.search_index {
+        let mut file = File::create(path).map_err(|e| write_error(path, &e))?;
+        SearchIndex::build(&categories, &render_opts)
+            .write_json(&mut file)
+            .map_err(|e| write_error(path, &e))?;
+    }
 
-    let mut writer = EmitterConfig::new()
-        .perform_indent(true)
-        .create_writer(io::stdout());
+    if let Some(path) = &opts.type_index {
+        let mut file = File::create(path).map_err(|e| write_error(path, &e))?;
+        TypeIndex::build(&categories, &render_opts)
+            .write_json(&mut file)
+            .map_err(|e| write_error(path, &e))?;
+    }
 
-    writer.write(
-        XmlEvent::start_element("section")
-            .attr("xmlns", "http://docbook.org/ns/docbook")
-            .attr("xmlns:xlink", "http://www.w3.org/1999/xlink")
-            .attr("xmlns:xi", "http://www.w3.org/2001/XInclude")
-            .attr("xml:id", format!("sec-functions-library-{}", opts.category).as_str()))
-        .unwrap();
+    match &opts.output_dir {
+        Some(dir) => {
+            check_distinct_categories(&categories)?;
+            fs::create_dir_all(dir).map_err(|e| write_error(dir, &e))?;
+
+            // HTML sites have an index page linking to all categories.
+            if format == Format::Html {
+                let path = dir.join("index.html");
+                let links = Links {
+                    all: &render_opts.categories,
+                    page: vec![],
+                    id_prefix: &render_opts.id_prefix,
+                    title_prefix: &render_opts.title_prefix,
+                    type_url: render_opts.type_url.as_deref(),
+                };
+                let descriptions: Vec<(String, String)> = categories.iter()
+                    .map(|c| (c.name.clone(), c.description.clone()))
+                    .collect();
+
+                let mut file = File::create(&path).map_err(|e| write_error(&path, &e))?;
+                write_index_html(&mut file, &descriptions, &links).map_err(|e| write_error(&path, &e))?;
+            }
 
-    writer.write(XmlEvent::comment(r#"Do not edit this file manually!
+            for category in categories {
+                let path = dir.join(format.file_name(&category.name));
+                let file = File::create(&path).map_err(|e| write_error(&path, &e))?;
+                write_document(file, format, vec![category], &render_opts).map_err(|e| write_error(&path, &e))?;
+            }
+        },
+
+        None => {
+            let stdout = io::stdout();
+            write_document(stdout.lock(), format, categories, &render_opts)
+                .map_err(|e| NixdocError::Write {
+                    target: "stdout".into(),
+                    message: e.to_string(),
+                })?;
+        },
+    }
 
-This file was generated using nixdoc[1]. Please edit the source Nix
-file from which this XML was generated instead.
+    lint_result
+}
 
-If you need to manually override the documentation of a single
-function in this file, create a new override file at
-`nixpkgs/docs/functions/library/overrides/<function-identifier>.xml`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Create an empty directory for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("nixdoc-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-[1]: https://github.com/tazjin/nixdoc
-"#)).unwrap();
+    #[test]
+    fn directories_are_expanded_in_order() {
+        let dir = test_dir("expand");
+        for file in &["strings.nix", "attrsets.nix", "README.md"] {
+            fs::write(dir.join(file), "{ }").unwrap();
+        }
+        fs::create_dir(dir.join("sub.nix")).unwrap();
 
-    writer.write(XmlEvent::start_element("title")).unwrap();
-    writer.write(XmlEvent::characters(&opts.description)).unwrap();
-    writer.write(XmlEvent::end_element()).unwrap();
+        let files = expand_paths(&[dir.clone(), PathBuf::from("lists.nix")]).unwrap();
+        assert_eq!(files, vec![dir.join("attrsets.nix"), dir.join("strings.nix"), PathBuf::from("lists.nix")]);
 
-    for entry in entries {
-        entry.write_section_xml(&mut writer).expect("Failed to write section")
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    writer.write(XmlEvent::end_element()).unwrap();
+    #[test]
+    fn configured_directories_are_expanded() {
+        let dir = test_dir("config-files");
+        for file in &["strings.nix", "attrsets.nix"] {
+            fs::write(dir.join(file), "{ }").unwrap();
+        }
+
+        let mut strings = FileConfig::new(dir.join("strings.nix"));
+        strings.category = Some("string".into());
+        let config = Config {
+            files: vec![strings.clone(), FileConfig::new(dir.clone()), FileConfig::new("lists.nix".into())],
+            ..Config::default()
+        };
+
+        let files: Vec<_> = config_files(&config).unwrap().into_iter()
+            .map(|file| (file.path, file.category))
+            .collect();
+        assert_eq!(files, vec![
+            (dir.join("strings.nix"), Some("string".into())),
+            (dir.join("attrsets.nix"), None),
+            (PathBuf::from("lists.nix"), None),
+        ]);
+
+        let mut described = FileConfig::new(dir.clone());
+        described.description = Some("Library functions".into());
+        let config = Config { files: vec![described], ..Config::default() };
+        assert!(config_files(&config).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flags_override_the_configuration() {
+        let config = Config {
+            title_prefix: Some("pkgs.lib.".into()),
+            id_prefix: Some("lib-".into()),
+            revision: Some("18.09".into()),
+            ..Config::default()
+        };
+
+        let opts = Options::from_iter(&["nixdoc", "--repo-url", "https://example.org/"]);
+        let render_opts = render_options(&opts, &config, &[]);
+        assert_eq!(render_opts.title_prefix, "pkgs.lib.");
+        assert_eq!(render_opts.id_prefix, "lib-");
+        assert_eq!(render_opts.location_url, Some("https://example.org/blob/18.09/{path}#L{line}".into()));
+
+        let opts = Options::from_iter(&["nixdoc", "--repo-url", "https://example.org", "--title-prefix", "lib.", "--revision", "19.03"]);
+        let render_opts = render_options(&opts, &config, &[]);
+        assert_eq!(render_opts.title_prefix, "lib.");
+        assert_eq!(render_opts.id_prefix, "lib-");
+        assert_eq!(render_opts.location_url, Some("https://example.org/blob/19.03/{path}#L{line}".into()));
+
+        let opts = Options::from_iter(&["nixdoc", "--repo-url", "https://example.org"]);
+        let render_opts = render_options(&opts, &Config::default(), &[]);
+        assert_eq!(render_opts.title_prefix, DEFAULT_TITLE_PREFIX);
+        assert_eq!(render_opts.location_url, Some("https://example.org/blob/master/{path}#L{line}".into()));
+    }
+
+    #[test]
+    fn duplicate_categories() {
+        let category = |name: &str| Category {
+            name: name.into(),
+            description: String::new(),
+            items: vec![],
+        };
+
+        assert!(check_distinct_categories(&[category("strings"), category("lists")]).is_ok());
+        match check_distinct_categories(&[category("strings"), category("lists"), category("strings")]) {
+            Err(NixdocError::Usage(message)) => assert!(message.contains("'strings'"), "{}", message),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,336 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements man page (roff) output for the manual
+//! entries, with one page per category, e.g. `nixpkgs-lib-strings(3)`.
+//!
+//! Pages have the usual NAME, SYNOPSIS (listing the type signatures),
+//! DESCRIPTION and EXAMPLES sections. Every function is a subsection
+//! of DESCRIPTION with its description and arguments, and of EXAMPLES
+//! if it has examples.
+
+use std::io::Write;
+use failure::Error;
+use pulldown_cmark::{Event, Parser, Tag};
+
+use docbook::{Argument, ManualEntry, SegmentKind};
+
+/// Section of the manual that pages are written for.
+pub const MAN_SECTION: &str = "3";
+
+/// Name of the man page of a category, e.g. `nixpkgs-lib-strings`.
+pub fn page_name(category: &str) -> String {
+    format!("nixpkgs-lib-{}", category)
+}
+
+/// Make sure that the output ends with a newline, so that a request
+/// can follow.
+fn newline(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Append a request (e.g. `.PP`) on its own line.
+fn request(out: &mut String, line: &str) {
+    newline(out);
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Append text, escaping backslashes, characters that would be
+/// interpreted as requests at the start of a line and hyphens, which
+/// are written as minus signs so that code can be copied from the page.
+fn text(out: &mut String, text: &str) {
+    for (idx, part) in text.split('\n').enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+
+        if (out.is_empty() || out.ends_with('\n')) && (part.starts_with('.') || part.starts_with('\'')) {
+            out.push_str("\\&");
+        }
+        out.push_str(&part.replace('\\', "\\e").replace('-', "\\-"));
+    }
+}
+
+/// Escape a single line of text, e.g. for use in a macro argument.
+fn escape(line: &str) -> String {
+    let mut out = String::new();
+    text(&mut out, line);
+    out.replace('"', "\\(dq")
+}
+
+/// Render Markdown as roff.
+fn markdown(source: &str) -> String {
+    let mut out = String::new();
+
+    // Number of the next item of each (nested) list, `None` for
+    // bullet lists.
+    let mut lists: Vec<Option<usize>> = vec![];
+
+    for event in Parser::new(source) {
+        match event {
+            Event::Start(Tag::Paragraph) => request(&mut out, ".PP"),
+            Event::Start(Tag::Header(_)) => {
+                request(&mut out, ".PP");
+                out.push_str("\\fB");
+            },
+            Event::End(Tag::Header(_)) => out.push_str("\\fR"),
+            Event::Start(Tag::CodeBlock(_)) => {
+                request(&mut out, ".PP");
+                request(&mut out, ".RS 4");
+                request(&mut out, ".nf");
+            },
+            Event::End(Tag::CodeBlock(_)) => {
+                request(&mut out, ".fi");
+                request(&mut out, ".RE");
+            },
+            Event::Start(Tag::List(start)) => lists.push(start),
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+            },
+            Event::Start(Tag::Item) => match lists.last_mut() {
+                Some(Some(number)) => {
+                    request(&mut out, &format!(".IP {}. 4", number));
+                    *number += 1;
+                },
+                _ => request(&mut out, ".IP \\(bu 2"),
+            },
+            Event::Start(Tag::BlockQuote) => request(&mut out, ".RS 4"),
+            Event::End(Tag::BlockQuote) => request(&mut out, ".RE"),
+            Event::Start(Tag::Emphasis) => out.push_str("\\fI"),
+            Event::Start(Tag::Strong) | Event::Start(Tag::Code) => out.push_str("\\fB"),
+            Event::End(Tag::Emphasis) | Event::End(Tag::Strong) | Event::End(Tag::Code) => {
+                out.push_str("\\fR")
+            },
+            Event::End(Tag::Link(ref url, _)) if !url.starts_with('#') => {
+                text(&mut out, &format!(" <{}>", url));
+            },
+            Event::Text(t) => text(&mut out, &t),
+            Event::SoftBreak => out.push('\n'),
+            Event::HardBreak => request(&mut out, ".br"),
+            _ => (),
+        }
+    }
+
+    out
+}
+
+/// Write the header of a page, up to its SYNOPSIS section, see
+/// `ManualEntry::collect_synopsis`.
+pub fn write_page_start<W: Write>(w: &mut W,
+                                  name: &str,
+                                  description: &str,
+                                  synopsis: &[String]) -> Result<(), Error> {
+    writeln!(w, ".\\\" Do not edit this file manually! It was generated using nixdoc.")?;
+    writeln!(w, ".TH \"{}\" \"{}\" \"\" \"nixdoc\" \"Nixpkgs Library Functions\"",
+             escape(&name.to_uppercase()), MAN_SECTION)?;
+    writeln!(w, ".SH NAME")?;
+    writeln!(w, "{} \\- {}", escape(name), escape(description))?;
+
+    if !synopsis.is_empty() {
+        writeln!(w, ".SH SYNOPSIS")?;
+        writeln!(w, ".nf")?;
+        for line in synopsis {
+            writeln!(w, "{}", escape(line))?;
+        }
+        writeln!(w, ".fi")?;
+    }
+
+    Ok(())
+}
+
+/// Write a section heading of a page, e.g. `DESCRIPTION`.
+pub fn write_heading_man<W: Write>(w: &mut W, heading: &str) -> Result<(), Error> {
+    writeln!(w, ".SH \"{}\"", escape(&heading.to_uppercase()))?;
+    Ok(())
+}
+
+impl Argument {
+    /// Write a tagged paragraph for a single function argument.
+    fn write_argument_man(self, out: &mut String, indent: bool) {
+        match self {
+            Argument::Flat(arg) => {
+                if indent {
+                    request(out, ".RS 4");
+                }
+                request(out, ".TP");
+                request(out, &format!("\\fB{}\\fR", escape(&arg.name)));
+                text(out, arg.doc.unwrap_or("Function argument".into()).trim());
+                if indent {
+                    request(out, ".RE");
+                }
+            },
+
+            Argument::Pattern(pattern_args) => {
+                request(out, ".TP");
+                request(out, "\\fBpattern\\fR");
+                text(out, "Structured function argument");
+                for pattern_arg in pattern_args {
+                    Argument::Flat(pattern_arg).write_argument_man(out, true);
+                }
+            },
+        }
+    }
+}
+
+impl ManualEntry {
+    /// Collect the lines of the SYNOPSIS section for this entry and
+    /// its children, which are the type signatures if present.
+    pub fn collect_synopsis(&self, lines: &mut Vec<String>) {
+        match &self.fn_type {
+            Some(t) if t.contains("::") => lines.push(t.clone()),
+            Some(t) => lines.push(format!("{} :: {}", self.title(), t)),
+            None => lines.push(self.title()),
+        }
+
+        for child in &self.children {
+            child.collect_synopsis(lines);
+        }
+    }
+
+    /// Collect the subsections of the EXAMPLES section for this entry
+    /// and its children, one for every entry with examples.
+    pub fn collect_examples_man(&self, out: &mut String) {
+        if !self.examples.is_empty() {
+            request(out, &format!(".SS \"{}\"", escape(&self.title())));
+        }
+
+        for example in &self.examples {
+            request(out, ".PP");
+            match &example.title {
+                Some(example_title) => {
+                    out.push_str("\\fIExample\\fR: ");
+                    text(out, example_title);
+                },
+                None => out.push_str("\\fIExample\\fR"),
+            }
+            request(out, ".RS 4");
+            request(out, ".nf");
+            if example.has_results() {
+                for segment in example.segments() {
+                    newline(out);
+                    match segment.kind {
+                        SegmentKind::Input => {
+                            for line in segment.text.lines() {
+                                newline(out);
+                                out.push_str("\\fB");
+                                text(out, line);
+                                out.push_str("\\fR");
+                            }
+                        },
+                        SegmentKind::Result => {
+                            out.push_str("=> ");
+                            text(out, &segment.text);
+                        },
+                    }
+                }
+            } else {
+                text(out, example.code.trim_end());
+            }
+            request(out, ".fi");
+            request(out, ".RE");
+        }
+
+        for child in &self.children {
+            child.collect_examples_man(out);
+        }
+    }
+
+    /// Write the subsection for a documented Nix function. Entries of
+    /// nested attribute sets follow their parent, as man pages only
+    /// have a single level of subsections. Examples are written
+    /// separately, see `collect_examples_man`.
+    pub fn write_section_man<W: Write>(self, w: &mut W) -> Result<(), Error> {
+        let title = self.title();
+        let mut out = String::new();
+
+        request(&mut out, &format!(".SS \"{}\"", escape(&title)));
+
+        // Type signature
+        if let Some(t) = &self.fn_type {
+            request(&mut out, ".PP");
+            out.push_str("\\fB");
+            text(&mut out, t);
+            out.push_str("\\fR");
+        }
+
+        // Reference to the original definition of re-exported entries
+        if let Some(alias) = &self.alias {
+            request(&mut out, ".PP");
+            text(&mut out, "Alias of ");
+            out.push_str("\\fB");
+            text(&mut out, &alias.name);
+            out.push_str("\\fR.");
+        }
+
+        // Primary doc string
+        out.push_str(&markdown(&self.description));
+
+        // Function arguments, in the style of an OPTIONS section
+        if !self.args.is_empty() {
+            request(&mut out, ".PP");
+            out.push_str("\\fIArguments\\fR");
+            for arg in self.args {
+                arg.write_argument_man(&mut out, false);
+            }
+        }
+
+        // Location of the definition
+        if let Some(location) = &self.location {
+            request(&mut out, ".PP");
+            text(&mut out, &format!("Located at {}", location.display()));
+            if let Some(url) = &self.location_url {
+                text(&mut out, &format!(" <{}>", url));
+            }
+            out.push('.');
+        }
+
+        newline(&mut out);
+        w.write_all(out.as_bytes())?;
+
+        // Members of nested attribute sets
+        for child in self.children {
+            child.write_section_man(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped() {
+        let mut out = String::new();
+        text(&mut out, ".TH x\nf :: a -> b\\c");
+        assert_eq!(out, "\\&.TH x\nf :: a \\-> b\\ec");
+    }
+
+    #[test]
+    fn code_blocks_escape_hyphens() {
+        let roff = markdown("```\nf --flag -1\n```");
+        assert!(roff.contains("f \\-\\-flag \\-1"), "{}", roff);
+    }
+
+    #[test]
+    fn ordered_lists() {
+        assert_eq!(markdown("3. a\n4. b\n   - c"), ".IP 3. 4\na\n.IP 4. 4\nb\n.IP \\(bu 2\nc");
+        assert_eq!(markdown("1. a\n- b"), ".IP 1. 4\na\n.IP \\(bu 2\nb");
+    }
+}
@@ -0,0 +1,438 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module implements searching library functions by their type
+//! signature, in the style of Hoogle.
+//!
+//! A query such as `[a] -> AttrSet` matches signatures that are equal
+//! to it up to the names of type variables and the order of function
+//! arguments. Matches that need to instantiate type variables, ignore
+//! additional arguments or reorder arguments are ranked lower.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use failure::Error;
+use serde_json;
+
+use docbook::ManualEntry;
+use types::{parse_signature, Field, Signature, Type};
+use {manual_entries, Category, RenderOptions};
+
+/// Cost of binding a type variable to a concrete type.
+const COST_INSTANTIATE: u32 = 1;
+
+/// Cost of matching a specific type with a generic one, e.g. an
+/// attribute set type with `AttrSet`.
+const COST_GENERALISE: u32 = 1;
+
+/// Cost of matching the arguments in a different order.
+const COST_REORDER: u32 = 1;
+
+/// Cost of every argument of a function that the query does not
+/// mention.
+const COST_EXTRA_ARGUMENT: u32 = 2;
+
+/// Functions with more arguments than this are only matched with
+/// their arguments in order, to bound the number of permutations.
+const MAX_REORDERED_ARGS: usize = 6;
+
+/// A function with a parsed type signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Identifier of the function's manual section.
+    pub ident: String,
+
+    /// Attribute path of the function, e.g. `lib.strings.concatStrings`.
+    pub name: String,
+
+    /// The type signature as written in the documentation.
+    pub doc_type: String,
+
+    /// The parsed type signature.
+    pub signature: Signature,
+}
+
+/// Search index over the type signatures of library functions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TypeIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+/// A function matching a query, with the cost of the match (lower is
+/// better).
+#[derive(Debug, Clone)]
+pub struct SearchResult<'a> {
+    pub entry: &'a IndexEntry,
+    pub cost: u32,
+}
+
+impl TypeIndex {
+    /// Build the index from the manual entries of the given
+    /// categories. Entries without a (well-formed) signature are
+    /// skipped.
+    pub fn build(categories: &[Category], opts: &RenderOptions) -> TypeIndex {
+        let mut index = TypeIndex::default();
+        for category in categories {
+            for entry in manual_entries(&category.name, category.items.clone(), opts) {
+                index.add_entry(&entry);
+            }
+        }
+        index
+    }
+
+    fn add_entry(&mut self, entry: &ManualEntry) {
+        if let Some(doc_type) = &entry.fn_type {
+            if let Ok(signature) = parse_signature(doc_type) {
+                self.entries.push(IndexEntry {
+                    ident: entry.ident(),
+                    name: entry.title(),
+                    doc_type: doc_type.clone(),
+                    signature,
+                });
+            }
+        }
+
+        for child in &entry.children {
+            self.add_entry(child);
+        }
+    }
+
+    /// Find all functions matching the query type, best matches first.
+    pub fn search(&self, query: &Type) -> Vec<SearchResult<'_>> {
+        let mut results: Vec<SearchResult> = self.entries.iter()
+            .filter_map(|entry| {
+                match_signature(query, &entry.signature.sig_type)
+                    .map(|cost| SearchResult { entry, cost })
+            })
+            .collect();
+
+        results.sort_by(|a, b| a.cost.cmp(&b.cost).then_with(|| a.entry.name.cmp(&b.entry.name)));
+        results
+    }
+
+    /// Write the index as JSON, for use by other tools.
+    pub fn write_json<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(&mut *w, self)?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    /// Read an index written by `write_json`.
+    pub fn read_json<R: Read>(r: R) -> Result<TypeIndex, Error> {
+        Ok(serde_json::from_reader(r)?)
+    }
+}
+
+/// Variable bindings established while matching a query with a
+/// candidate signature, in both directions.
+#[derive(Debug, Clone, Default)]
+struct Bindings {
+    query: HashMap<String, Type>,
+    candidate: HashMap<String, Type>,
+}
+
+/// Normalise a type name, so that the various spellings used in lib
+/// (e.g. `AttrSet`, `attrs` and `attrset`) match each other.
+fn normalise_name(name: &str) -> String {
+    let lower = name.to_lowercase();
+    let normalised = match lower.as_str() {
+        "attrset" | "attrs" | "set" => "attrs",
+        "str" | "string" => "string",
+        "int" | "integer" => "int",
+        "bool" | "boolean" => "bool",
+        "lambda" | "function" => "function",
+        "drv" | "derivation" | "package" => "derivation",
+        other => other,
+    };
+    normalised.to_string()
+}
+
+/// Normalise the names of all named types in a type, see
+/// `normalise_name`.
+fn normalise(t: &Type) -> Type {
+    match t {
+        Type::Var { .. } => t.clone(),
+        Type::Name { name, args } => Type::Name {
+            name: normalise_name(name),
+            args: args.iter().map(normalise).collect(),
+        },
+        Type::List { element } => Type::List { element: Box::new(normalise(element)) },
+        Type::AttrSet { fields, open } => Type::AttrSet {
+            fields: fields.iter()
+                .map(|field| Field { field_type: normalise(&field.field_type), ..field.clone() })
+                .collect(),
+            open: *open,
+        },
+        Type::Function { argument, result } => Type::Function {
+            argument: Box::new(normalise(argument)),
+            result: Box::new(normalise(result)),
+        },
+        Type::Union { alternatives } => Type::Union {
+            alternatives: alternatives.iter().map(normalise).collect(),
+        },
+    }
+}
+
+/// Bind a variable to a type, or check the type against an existing
+/// binding. Binding to another variable is free, as variables can be
+/// renamed. Types are bound in their normalised form, so that e.g.
+/// `AttrSet` matches a variable bound to `attrs`.
+fn bind(bindings: &mut HashMap<String, Type>, name: &str, to: &Type) -> Option<u32> {
+    let to = normalise(to);
+    if let Some(bound) = bindings.get(name) {
+        return if *bound == to { Some(0) } else { None };
+    }
+
+    bindings.insert(name.to_string(), to.clone());
+    match to {
+        Type::Var { .. } => Some(0),
+        _ => Some(COST_INSTANTIATE),
+    }
+}
+
+/// Match two types structurally, returning the cost of the match.
+fn unify(query: &Type, candidate: &Type, bindings: &mut Bindings) -> Option<u32> {
+    match (query, candidate) {
+        (Type::Var { name: a }, Type::Var { name: b }) => {
+            let cost = bind(&mut bindings.query, a, candidate)?;
+            Some(cost + bind(&mut bindings.candidate, b, query)?)
+        },
+        (Type::Var { name }, _) => bind(&mut bindings.query, name, candidate),
+        (_, Type::Var { name }) => bind(&mut bindings.candidate, name, query),
+
+        (Type::Name { name: a, args: a_args }, Type::Name { name: b, args: b_args }) => {
+            if normalise_name(a) != normalise_name(b) || a_args.len() != b_args.len() {
+                return None;
+            }
+
+            let mut cost = 0;
+            for (a, b) in a_args.iter().zip(b_args) {
+                cost += unify(a, b, bindings)?;
+            }
+            Some(cost)
+        },
+
+        (Type::List { element: a }, Type::List { element: b }) => unify(a, b, bindings),
+
+        (Type::AttrSet { fields: a, .. }, Type::AttrSet { fields: b, open }) => {
+            let mut cost = 0;
+            for field in a {
+                match b.iter().find(|f| f.name == field.name) {
+                    Some(other) => cost += unify(&field.field_type, &other.field_type, bindings)?,
+                    None if *open => cost += COST_GENERALISE,
+                    None => return None,
+                }
+            }
+
+            // Attributes that the query does not mention
+            let missing = b.iter().filter(|f| !a.iter().any(|g| g.name == f.name)).count();
+            Some(cost + missing as u32 * COST_GENERALISE)
+        },
+
+        // Specific attribute sets and functions match their generic
+        // named counterparts.
+        (Type::AttrSet { .. }, Type::Name { name, args }) |
+        (Type::Name { name, args }, Type::AttrSet { .. })
+            if args.is_empty() && normalise_name(name) == "attrs" => Some(COST_GENERALISE),
+
+        (Type::Function { .. }, Type::Name { name, args }) |
+        (Type::Name { name, args }, Type::Function { .. })
+            if args.is_empty() && normalise_name(name) == "function" => Some(COST_GENERALISE),
+
+        (Type::Function { .. }, Type::Function { .. }) => match_function(query, candidate, bindings),
+
+        (Type::Union { alternatives: a }, Type::Union { alternatives: b }) => {
+            if a.len() != b.len() {
+                return None;
+            }
+
+            let mut cost = 0;
+            for (a, b) in a.iter().zip(b) {
+                cost += unify(a, b, bindings)?;
+            }
+            Some(cost)
+        },
+
+        // A union matches any of its alternatives.
+        (_, Type::Union { alternatives }) => alternatives.iter()
+            .filter_map(|alternative| {
+                let mut attempt = bindings.clone();
+                let cost = unify(query, alternative, &mut attempt)?;
+                Some((cost + COST_GENERALISE, attempt))
+            })
+            .min_by_key(|(cost, _)| *cost)
+            .map(|(cost, attempt)| {
+                *bindings = attempt;
+                cost
+            }),
+
+        _ => None,
+    }
+}
+
+/// Split a curried function type into its arguments and result.
+fn split_function(t: &Type) -> (Vec<&Type>, &Type) {
+    let mut args = vec![];
+    let mut current = t;
+    while let Type::Function { argument, result } = current {
+        args.push(&**argument);
+        current = result;
+    }
+    (args, current)
+}
+
+/// Match two function types, allowing the candidate's arguments to
+/// be in a different order and to include arguments that the query
+/// does not mention.
+fn match_function(query: &Type, candidate: &Type, bindings: &mut Bindings) -> Option<u32> {
+    let (query_args, query_result) = split_function(query);
+    let (candidate_args, candidate_result) = split_function(candidate);
+
+    if query_args.len() > candidate_args.len() {
+        return None;
+    }
+
+    let extra = (candidate_args.len() - query_args.len()) as u32 * COST_EXTRA_ARGUMENT;
+    let reorder = candidate_args.len() <= MAX_REORDERED_ARGS;
+
+    let mut used = vec![false; candidate_args.len()];
+    let (cost, result) = match_args(&query_args, &candidate_args, 0, &mut used, reorder, bindings)?;
+
+    let mut result = result;
+    let result_cost = unify(query_result, candidate_result, &mut result)?;
+    *bindings = result;
+
+    Some(cost + result_cost + extra)
+}
+
+/// Assign each query argument (starting at `idx`) to a distinct
+/// candidate argument, returning the cheapest assignment.
+fn match_args(query: &[&Type],
+              candidate: &[&Type],
+              idx: usize,
+              used: &mut [bool],
+              reorder: bool,
+              bindings: &Bindings) -> Option<(u32, Bindings)> {
+    if idx == query.len() {
+        return Some((0, bindings.clone()));
+    }
+
+    let mut best: Option<(u32, Bindings)> = None;
+
+    for position in 0..candidate.len() {
+        if used[position] || (!reorder && position != idx) {
+            continue;
+        }
+
+        let mut attempt = bindings.clone();
+        let cost = match unify(query[idx], candidate[position], &mut attempt) {
+            Some(cost) => cost + if position == idx { 0 } else { COST_REORDER },
+            None => continue,
+        };
+
+        used[position] = true;
+        let rest = match_args(query, candidate, idx + 1, used, reorder, &attempt);
+        used[position] = false;
+
+        if let Some((rest_cost, rest_bindings)) = rest {
+            if best.as_ref().map(|(best_cost, _)| cost + rest_cost < *best_cost).unwrap_or(true) {
+                best = Some((cost + rest_cost, rest_bindings));
+            }
+        }
+    }
+
+    best
+}
+
+/// Match a query type against the type of a candidate signature,
+/// returning the cost of the match if they match at all.
+pub fn match_signature(query: &Type, candidate: &Type) -> Option<u32> {
+    unify(query, candidate, &mut Bindings::default())
+}
+
+/// Write the results of a search, one function per line.
+pub fn write_results<W: Write>(w: &mut W, results: &[SearchResult]) -> Result<(), Error> {
+    for result in results {
+        writeln!(w, "{:>3}  {} :: {}", result.cost, result.entry.name, result.entry.signature.sig_type)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, doc_type: &str) -> IndexEntry {
+        IndexEntry {
+            ident: format!("lib.{}", name),
+            name: format!("lib.{}", name),
+            doc_type: doc_type.into(),
+            signature: parse_signature(doc_type).unwrap(),
+        }
+    }
+
+    fn search(index: &TypeIndex, query: &str) -> Vec<(String, u32)> {
+        index.search(&parse_signature(query).unwrap().sig_type).into_iter()
+            .map(|result| (result.entry.name.clone(), result.cost))
+            .collect()
+    }
+
+    fn cost(query: &str, candidate: &str) -> Option<u32> {
+        match_signature(&parse_signature(query).unwrap().sig_type,
+                        &parse_signature(candidate).unwrap().sig_type)
+    }
+
+    #[test]
+    fn renamed_variables_match_exactly() {
+        assert_eq!(cost("(a -> b) -> [a] -> [b]", "(x -> y) -> [x] -> [y]"), Some(0));
+        assert_eq!(cost("a -> b", "b -> a"), Some(0));
+        assert_eq!(cost("a -> b -> a", "x -> x -> x"), None);
+    }
+
+    #[test]
+    fn synonyms_match() {
+        assert_eq!(cost("AttrSet -> attrs", "attrs -> AttrSet"), Some(0));
+        assert_eq!(cost("a -> a", "AttrSet -> attrs"), Some(1));
+        assert_eq!(cost("[a] -> a -> bool", "[attrs] -> AttrSet -> Bool"), Some(1));
+    }
+
+    #[test]
+    fn costs() {
+        assert_eq!(cost("String -> [String]", "String -> [a]"), Some(1));
+        assert_eq!(cost("[a] -> Int", "Int -> [a] -> Int"), Some(3));
+        assert_eq!(cost("[a] -> Int -> Int", "Int -> [a] -> Int"), Some(2));
+        assert_eq!(cost("{ name :: String; } -> Bool", "AttrSet -> Bool"), Some(1));
+        assert_eq!(cost("Int -> Int", "String -> Int"), None);
+    }
+
+    #[test]
+    fn ranking() {
+        let index = TypeIndex {
+            entries: vec![
+                entry("attrsets.mapAttrs", "(String -> a -> b) -> AttrSet -> AttrSet"),
+                entry("attrsets.listToAttrs", "[{ name :: String; value :: a; }] -> AttrSet"),
+                entry("lists.foldl", "(b -> a -> b) -> b -> [a] -> b"),
+                entry("lists.length", "[a] -> Int"),
+                entry("attrsets.genAttrs", "[String] -> (String -> a) -> AttrSet"),
+            ],
+        };
+
+        assert_eq!(search(&index, "[a] -> AttrSet"), vec![
+            ("lib.attrsets.listToAttrs".to_string(), 1),
+            ("lib.attrsets.genAttrs".to_string(), 3),
+            ("lib.lists.foldl".to_string(), 6),
+        ]);
+    }
+}
@@ -0,0 +1,622 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module parses the informal type signatures given in `Type:`
+//! lines of doc comments, e.g.
+//!
+//! ```text
+//! foldl :: (b -> a -> b) -> b -> [a] -> b
+//! mapAttrs :: (String -> a -> b) -> AttrSet -> AttrSet
+//! mkOption :: { default :: a; description :: String; ... } -> Option
+//! toInt :: String -> Int | Null
+//! ```
+//!
+//! Lowercase names that are not well-known types (such as `string`
+//! or `bool`) are type variables. Names followed by other types are
+//! type applications, e.g. `AttrsOf a` or `listOf string`.
+
+use std::fmt;
+
+/// Lowercase names used for concrete types in lib, which are
+/// therefore not type variables.
+const KNOWN_TYPES: &[&str] = &[
+    "any", "attrs", "attrset", "bool", "derivation", "float", "function",
+    "int", "lambda", "list", "null", "number", "package", "path", "string",
+];
+
+/// A type in a signature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Type {
+    /// A type variable, e.g. `a`.
+    Var { name: String },
+
+    /// A named type, possibly applied to arguments, e.g. `Int` or
+    /// `AttrsOf a`.
+    Name { name: String, args: Vec<Type> },
+
+    /// A list with elements of the given type, e.g. `[a]`.
+    List { element: Box<Type> },
+
+    /// An attribute set with the given attributes. Open sets (with a
+    /// trailing `...`) may contain further attributes.
+    AttrSet { fields: Vec<Field>, open: bool },
+
+    /// A function, e.g. `a -> b`.
+    Function { argument: Box<Type>, result: Box<Type> },
+
+    /// One of several types, e.g. `Int | Null`.
+    Union { alternatives: Vec<Type> },
+}
+
+/// An attribute of an attribute set type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub optional: bool,
+    #[serde(rename = "type")]
+    pub field_type: Type,
+}
+
+/// A parsed type signature, optionally naming what it describes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub sig_type: Type,
+}
+
+/// Error in a malformed type signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    /// Byte offset of the error in the signature.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Arrow,
+    DoubleColon,
+    Pipe,
+    Question,
+    Ellipsis,
+    Separator,
+    Open(char),
+    Close(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "`{}`", name),
+            Token::Arrow => write!(f, "`->`"),
+            Token::DoubleColon => write!(f, "`::`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Question => write!(f, "`?`"),
+            Token::Ellipsis => write!(f, "`...`"),
+            Token::Separator => write!(f, "separator"),
+            Token::Open(c) | Token::Close(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\'' || c == '.'
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, TypeError> {
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '-' if chars.peek().map(|&(_, c)| c) == Some('>') => {
+                chars.next();
+                Token::Arrow
+            },
+            ':' if chars.peek().map(|&(_, c)| c) == Some(':') => {
+                chars.next();
+                Token::DoubleColon
+            },
+            '.' if src[offset..].starts_with("...") => {
+                chars.next();
+                chars.next();
+                Token::Ellipsis
+            },
+            '|' => Token::Pipe,
+            '?' => Token::Question,
+            ';' | ',' => Token::Separator,
+            '(' | '[' | '{' => Token::Open(c),
+            ')' | ']' | '}' => Token::Close(c),
+            c if is_ident_char(c) => {
+                let mut end = offset + c.len_utf8();
+                while let Some(&(idx, c)) = chars.peek() {
+                    // Dots are part of qualified names such as
+                    // `lib.types.str`, but not of a following `...`.
+                    if !is_ident_char(c) || src[idx..].starts_with("...") {
+                        break;
+                    }
+                    end = idx + c.len_utf8();
+                    chars.next();
+                }
+                Token::Ident(src[offset..end].to_string())
+            },
+            _ => return Err(TypeError {
+                offset,
+                message: format!("unexpected character `{}`", c),
+            }),
+        };
+
+        tokens.push((offset, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of a signature.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+
+    /// Byte ranges of the named types parsed so far.
+    names: Vec<(usize, usize)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(o, _)| *o)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, TypeError> {
+        Err(TypeError { offset: self.offset(), message })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, TypeError> {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {}, found {}", expected, token)),
+            None => self.error(format!("expected {}, found end of signature", expected)),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), TypeError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            self.unexpected(&token.to_string())
+        }
+    }
+
+    /// `type := union ('->' type)?`
+    fn parse_type(&mut self) -> Result<Type, TypeError> {
+        let argument = self.parse_union()?;
+
+        if self.eat(&Token::Arrow) {
+            let result = self.parse_type()?;
+            return Ok(Type::Function {
+                argument: Box::new(argument),
+                result: Box::new(result),
+            });
+        }
+
+        Ok(argument)
+    }
+
+    /// `union := application ('|' application)*`
+    fn parse_union(&mut self) -> Result<Type, TypeError> {
+        let mut alternatives = vec![self.parse_application()?];
+        while self.eat(&Token::Pipe) {
+            alternatives.push(self.parse_application()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.remove(0))
+        } else {
+            Ok(Type::Union { alternatives })
+        }
+    }
+
+    fn starts_atom(&self) -> bool {
+        matches!(self.peek(), Some(Token::Ident(_)) | Some(Token::Open(_)))
+    }
+
+    /// `application := name atom* | atom`
+    ///
+    /// Type variables followed by arguments are names of type
+    /// constructors, e.g. `attrsOf` in `attrsOf a`.
+    fn parse_application(&mut self) -> Result<Type, TypeError> {
+        let start = self.offset();
+        let head = match self.parse_atom()? {
+            Type::Var { name } if self.starts_atom() => {
+                self.names.push((start, start + name.len()));
+                Type::Name { name, args: vec![] }
+            },
+            head => head,
+        };
+
+        match head {
+            Type::Name { name, .. } => {
+                let mut args = vec![];
+                while self.starts_atom() {
+                    args.push(self.parse_atom()?);
+                }
+                Ok(Type::Name { name, args })
+            },
+            other => Ok(other),
+        }
+    }
+
+    /// `atom := ident | '[' type ']' | '(' type ')' | '{' fields '}'`
+    fn parse_atom(&mut self) -> Result<Type, TypeError> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                let start = self.offset();
+                self.pos += 1;
+                if name.starts_with(char::is_lowercase) && !KNOWN_TYPES.contains(&name.as_str()) {
+                    Ok(Type::Var { name })
+                } else {
+                    self.names.push((start, start + name.len()));
+                    Ok(Type::Name { name, args: vec![] })
+                }
+            },
+
+            Some(Token::Open('[')) => {
+                self.pos += 1;
+                let element = self.parse_type()?;
+                self.expect(Token::Close(']'))?;
+                Ok(Type::List { element: Box::new(element) })
+            },
+
+            Some(Token::Open('(')) => {
+                self.pos += 1;
+                let inner = self.parse_type()?;
+                self.expect(Token::Close(')'))?;
+                Ok(inner)
+            },
+
+            Some(Token::Open('{')) => {
+                self.pos += 1;
+                self.parse_fields()
+            },
+
+            _ => self.unexpected("a type"),
+        }
+    }
+
+    /// `fields := (field separator)* '...'? '}'`, where the last
+    /// separator is optional.
+    fn parse_fields(&mut self) -> Result<Type, TypeError> {
+        let mut fields = vec![];
+        let mut open = false;
+
+        loop {
+            if self.eat(&Token::Close('}')) {
+                break;
+            }
+
+            if self.eat(&Token::Ellipsis) {
+                open = true;
+                self.eat(&Token::Separator);
+                self.expect(Token::Close('}'))?;
+                break;
+            }
+
+            let name = match self.peek().cloned() {
+                Some(Token::Ident(name)) => name,
+                _ => return self.unexpected("an attribute name"),
+            };
+            self.pos += 1;
+
+            let optional = self.eat(&Token::Question);
+            self.expect(Token::DoubleColon)?;
+            let field_type = self.parse_type()?;
+            fields.push(Field { name, optional, field_type });
+
+            if !self.eat(&Token::Separator) && self.peek() != Some(&Token::Close('}')) {
+                return self.unexpected("`;` or `}`");
+            }
+        }
+
+        Ok(Type::AttrSet { fields, open })
+    }
+}
+
+/// Parse a type signature such as `map :: (a -> b) -> [a] -> [b]`.
+/// The name preceding `::` is optional.
+pub fn parse_signature(src: &str) -> Result<Signature, TypeError> {
+    parse_with_names(src).map(|(signature, _)| signature)
+}
+
+/// Split a type signature into fragments of its text, flagging those
+/// that are the names of types (rather than type variables, attribute
+/// names or punctuation) so that renderers can link them. Malformed
+/// signatures are a single unflagged fragment.
+pub fn split_type_names(src: &str) -> Vec<(&str, bool)> {
+    let names = match parse_with_names(src) {
+        Ok((_, names)) => names,
+        Err(_) => return vec![(src, false)],
+    };
+
+    let mut fragments = vec![];
+    let mut last = 0;
+    for (start, end) in names {
+        if start > last {
+            fragments.push((&src[last..start], false));
+        }
+        fragments.push((&src[start..end], true));
+        last = end;
+    }
+
+    if last < src.len() {
+        fragments.push((&src[last..], false));
+    }
+
+    fragments
+}
+
+/// Parse a type signature, also returning the byte ranges of the
+/// named types in it in order.
+fn parse_with_names(src: &str) -> Result<(Signature, Vec<(usize, usize)>), TypeError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser { tokens, pos: 0, end: src.len(), names: vec![] };
+
+    let name = match (parser.tokens.first(), parser.tokens.get(1)) {
+        (Some((_, Token::Ident(name))), Some((_, Token::DoubleColon))) => Some(name.clone()),
+        _ => None,
+    };
+    if name.is_some() {
+        parser.pos = 2;
+    }
+
+    let sig_type = parser.parse_type()?;
+    if parser.peek().is_some() {
+        return parser.unexpected("end of signature");
+    }
+
+    parser.names.sort();
+    Ok((Signature { name, sig_type }, parser.names))
+}
+
+impl Type {
+    /// Number of arguments of a (curried) function type.
+    pub fn arity(&self) -> usize {
+        match self {
+            Type::Function { result, .. } => 1 + result.arity(),
+            _ => 0,
+        }
+    }
+
+    /// Names of all named types referenced in this type, in order of
+    /// their first occurrence.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Type::Var { .. } => (),
+            Type::Name { name, args } => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+                for arg in args {
+                    arg.collect_names(names);
+                }
+            },
+            Type::List { element } => element.collect_names(names),
+            Type::AttrSet { fields, .. } => for field in fields {
+                field.field_type.collect_names(names);
+            },
+            Type::Function { argument, result } => {
+                argument.collect_names(names);
+                result.collect_names(names);
+            },
+            Type::Union { alternatives } => for alternative in alternatives {
+                alternative.collect_names(names);
+            },
+        }
+    }
+
+    /// Whether the type needs parentheses when used in a position
+    /// with the given precedence (0: function result, 1: function
+    /// argument, 2: union alternative, 3: type argument).
+    fn needs_parens(&self, precedence: u8) -> bool {
+        match self {
+            Type::Function { .. } => precedence >= 1,
+            Type::Union { .. } => precedence >= 2,
+            Type::Name { args, .. } => !args.is_empty() && precedence >= 3,
+            _ => false,
+        }
+    }
+
+    fn fmt_in(&self, f: &mut fmt::Formatter, precedence: u8) -> fmt::Result {
+        if self.needs_parens(precedence) {
+            write!(f, "(")?;
+            self.fmt_in(f, 0)?;
+            return write!(f, ")");
+        }
+
+        match self {
+            Type::Var { name } => write!(f, "{}", name),
+            Type::Name { name, args } => {
+                write!(f, "{}", name)?;
+                for arg in args {
+                    write!(f, " ")?;
+                    arg.fmt_in(f, 3)?;
+                }
+                Ok(())
+            },
+            Type::List { element } => {
+                write!(f, "[")?;
+                element.fmt_in(f, 0)?;
+                write!(f, "]")
+            },
+            Type::AttrSet { fields, open } => {
+                write!(f, "{{")?;
+                for field in fields {
+                    write!(f, " {}{} :: ", field.name, if field.optional { "?" } else { "" })?;
+                    field.field_type.fmt_in(f, 0)?;
+                    write!(f, ";")?;
+                }
+                if *open {
+                    write!(f, " ...")?;
+                }
+                write!(f, " }}")
+            },
+            Type::Function { argument, result } => {
+                argument.fmt_in(f, 1)?;
+                write!(f, " -> ")?;
+                result.fmt_in(f, 0)
+            },
+            Type::Union { alternatives } => {
+                for (idx, alternative) in alternatives.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " | ")?;
+                    }
+                    alternative.fmt_in(f, 2)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Types are displayed in a normalised form of the signature syntax.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_in(f, 0)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "{} :: ", name)?;
+        }
+        write!(f, "{}", self.sig_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Type {
+        parse_signature(src).expect("signature should parse").sig_type
+    }
+
+    fn var(name: &str) -> Type {
+        Type::Var { name: name.into() }
+    }
+
+    fn name(name: &str, args: Vec<Type>) -> Type {
+        Type::Name { name: name.into(), args }
+    }
+
+    fn function(argument: Type, result: Type) -> Type {
+        Type::Function { argument: Box::new(argument), result: Box::new(result) }
+    }
+
+    #[test]
+    fn arrows() {
+        let signature = parse_signature("foldl :: (b -> a -> b) -> b -> [a] -> b").unwrap();
+        assert_eq!(signature.name, Some("foldl".into()));
+        assert_eq!(signature.sig_type.arity(), 3);
+        assert_eq!(signature.to_string(), "foldl :: (b -> a -> b) -> b -> [a] -> b");
+        assert_eq!(parse("a -> b -> c"), function(var("a"), function(var("b"), var("c"))));
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(parse("[String]"), Type::List { element: Box::new(name("String", vec![])) });
+        assert_eq!(parse("[[a]]").to_string(), "[[a]]");
+    }
+
+    #[test]
+    fn records() {
+        let record = parse("{ name :: String; value ? :: a; ... }");
+        assert_eq!(record, Type::AttrSet {
+            fields: vec![
+                Field { name: "name".into(), optional: false, field_type: name("String", vec![]) },
+                Field { name: "value".into(), optional: true, field_type: var("a") },
+            ],
+            open: true,
+        });
+        assert_eq!(parse("{ a :: int }").to_string(), "{ a :: int; }");
+        assert_eq!(parse("{ a :: int; ... }"), parse("{ a :: int;... }"));
+        assert_eq!(tokenize("a...").unwrap(),
+                   vec![(0, Token::Ident("a".into())), (1, Token::Ellipsis)]);
+    }
+
+    #[test]
+    fn unions() {
+        assert_eq!(parse("String -> Int | Null"), function(
+            name("String", vec![]),
+            Type::Union { alternatives: vec![name("Int", vec![]), name("Null", vec![])] },
+        ));
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(parse("AttrsOf a"), name("AttrsOf", vec![var("a")]));
+        assert_eq!(parse("attrsOf a"), name("attrsOf", vec![var("a")]));
+        assert_eq!(parse("listOf string"), name("listOf", vec![name("string", vec![])]));
+        assert_eq!(parse("nullOr int -> int"), function(name("nullOr", vec![name("int", vec![])]), name("int", vec![])));
+        assert_eq!(parse("either (listOf a) b").to_string(), "either (listOf a) b");
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse_signature("a ->").is_err());
+        assert!(parse_signature("[a").is_err());
+        assert!(parse_signature("{ a :: int b :: int }").is_err());
+        assert!(parse_signature("a -> b)").is_err());
+        assert_eq!(parse_signature("a $ b").unwrap_err().offset, 2);
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(split_type_names("map :: (a -> b) -> [a] -> AttrSet"),
+                   vec![("map :: (a -> b) -> [a] -> ", false), ("AttrSet", true)]);
+        assert_eq!(split_type_names("listOf string"),
+                   vec![("listOf", true), (" ", false), ("string", true)]);
+        assert_eq!(split_type_names("{ int :: a }"), vec![("{ int :: a }", false)]);
+        assert_eq!(split_type_names("a ->"), vec![("a ->", false)]);
+    }
+}